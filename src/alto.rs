@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use crate::file_processors::PageResult;
+use crate::ocr_engine::{BoundingBox, OcrLine};
+use crate::utils::escape_xml;
+
+fn position_attrs(bbox: &BoundingBox) -> String {
+    format!(
+        "HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        bbox.left, bbox.top, bbox.width, bbox.height
+    )
+}
+
+/// Render one OCR'd page as ALTO v4 XML (pixel units).
+/// Returns `None` when the page has no OCR layout.
+pub fn render_alto(filename: &str, page: &PageResult) -> Option<String> {
+    let ocr = page.ocr.as_ref()?;
+    let n = page.page_number;
+
+    let (width, height) = ocr.pages.first().map(|p| (p.width, p.height)).unwrap_or_default();
+    let lines = ocr.lines();
+    let print_space = lines
+        .iter()
+        .map(|l| l.bbox)
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or(BoundingBox { left: 0, top: 0, width, height });
+
+    // ALTO has no paragraph level: Tesseract blocks become TextBlocks
+    let mut blocks: BTreeMap<u32, Vec<&OcrLine>> = BTreeMap::new();
+    for line in &lines {
+        blocks.entry(line.block_num).or_default().push(line);
+    }
+
+    let mut alto = String::new();
+    alto.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    alto.push_str("<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" \
+                   xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+                   xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+                   xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# \
+                   http://www.loc.gov/alto/v4/alto-4-2.xsd\">\n");
+    alto.push_str("  <Description>\n");
+    alto.push_str("    <MeasurementUnit>pixel</MeasurementUnit>\n");
+    alto.push_str("    <sourceImageInformation>\n");
+    alto.push_str(&format!("      <fileName>{}</fileName>\n", escape_xml(filename)));
+    alto.push_str("    </sourceImageInformation>\n");
+    alto.push_str("    <OCRProcessing ID=\"OCR_0\">\n");
+    alto.push_str("      <ocrProcessingStep>\n");
+    alto.push_str("        <processingSoftware>\n");
+    alto.push_str("          <softwareName>tesseract</softwareName>\n");
+    alto.push_str(&format!("          <applicationDescription>advanced-ocr {}</applicationDescription>\n",
+                           env!("CARGO_PKG_VERSION")));
+    alto.push_str("        </processingSoftware>\n");
+    alto.push_str("      </ocrProcessingStep>\n");
+    alto.push_str("    </OCRProcessing>\n");
+    alto.push_str("  </Description>\n");
+    alto.push_str("  <Layout>\n");
+
+    let lang = page
+        .language
+        .as_deref()
+        .map(|l| format!(" LANG=\"{}\"", escape_xml(l)))
+        .unwrap_or_default();
+
+    alto.push_str(&format!(
+        "    <Page ID=\"page_{}\" PHYSICAL_IMG_NR=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\">\n",
+        n, n, width, height
+    ));
+    alto.push_str(&format!("      <PrintSpace {}>\n", position_attrs(&print_space)));
+
+    let mut line_id = 0;
+
+    for (block_num, block_lines) in &blocks {
+        let block_bbox = block_lines
+            .iter()
+            .map(|l| l.bbox)
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default();
+        alto.push_str(&format!(
+            "        <TextBlock ID=\"block_{}_{}\" {}{}>\n",
+            n, block_num, position_attrs(&block_bbox), lang
+        ));
+
+        for line in block_lines {
+            line_id += 1;
+            alto.push_str(&format!(
+                "          <TextLine ID=\"line_{}_{}\" {}>\n",
+                n, line_id, position_attrs(&line.bbox)
+            ));
+
+            for (i, word) in line.words.iter().enumerate() {
+                if i > 0 {
+                    // Gap between the previous word and this one
+                    let previous = &line.words[i - 1].bbox;
+                    let gap = word.bbox.left.saturating_sub(previous.right());
+                    alto.push_str(&format!(
+                        "            <SP WIDTH=\"{}\" HPOS=\"{}\" VPOS=\"{}\"/>\n",
+                        gap, previous.right(), line.bbox.top
+                    ));
+                }
+
+                // Tesseract numbers words within their line
+                alto.push_str(&format!(
+                    "            <String ID=\"string_{}_{}_{}_{}_{}\" CONTENT=\"{}\" {} WC=\"{:.2}\"/>\n",
+                    n,
+                    word.block_num,
+                    word.par_num,
+                    word.line_num,
+                    word.word_num,
+                    escape_xml(&word.text),
+                    position_attrs(&word.bbox),
+                    (word.confidence / 100.0).clamp(0.0, 1.0)
+                ));
+            }
+
+            alto.push_str("          </TextLine>\n");
+        }
+
+        alto.push_str("        </TextBlock>\n");
+    }
+
+    alto.push_str("      </PrintSpace>\n");
+    alto.push_str("    </Page>\n");
+    alto.push_str("  </Layout>\n");
+    alto.push_str("</alto>\n");
+
+    Some(alto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr_engine::{OcrAnalysisResult, OcrPage, OcrWordResult};
+
+    fn word(text: &str, word_num: u32, left: u32, confidence: f32) -> OcrWordResult {
+        OcrWordResult {
+            text: text.to_string(),
+            confidence,
+            page_num: 1,
+            block_num: 1,
+            par_num: 1,
+            line_num: 1,
+            word_num,
+            bbox: BoundingBox { left, top: 20, width: 40, height: 12 },
+        }
+    }
+
+    #[test]
+    fn strings_carry_positions_and_confidences() {
+        let analysis = OcrAnalysisResult {
+            text: "\"Smith\" & Co".to_string(),
+            pages: vec![OcrPage { page_num: 1, width: 300, height: 200 }],
+            words: vec![word("\"Smith\"", 1, 10, 93.0), word("&", 2, 60, 41.0), word("Co", 3, 110, 100.0)],
+            avg_confidence: 78.0,
+            detected_language: Some("eng".to_string()),
+            language_confidence: Some(0.9),
+            rotation: 0,
+            skew_angle: 0.0,
+        };
+        let page = PageResult::from_ocr(2, analysis);
+
+        let alto = render_alto("scan.tif", &page).unwrap();
+
+        assert!(alto.contains("<Page ID=\"page_2\" PHYSICAL_IMG_NR=\"2\" WIDTH=\"300\" HEIGHT=\"200\">"));
+        assert!(alto.contains("<TextBlock ID=\"block_2_1\" HPOS=\"10\" VPOS=\"20\" WIDTH=\"140\" HEIGHT=\"12\" LANG=\"eng\">"));
+        assert!(alto.contains(
+            "<String ID=\"string_2_1_1_1_1\" CONTENT=\"&quot;Smith&quot;\" HPOS=\"10\" VPOS=\"20\" WIDTH=\"40\" HEIGHT=\"12\" WC=\"0.93\"/>"
+        ));
+        assert!(alto.contains("<SP WIDTH=\"10\" HPOS=\"50\" VPOS=\"20\"/>"));
+        assert!(alto.contains(
+            "<String ID=\"string_2_1_1_1_2\" CONTENT=\"&amp;\" HPOS=\"60\" VPOS=\"20\" WIDTH=\"40\" HEIGHT=\"12\" WC=\"0.41\"/>"
+        ));
+        assert!(alto.contains("CONTENT=\"Co\" HPOS=\"110\" VPOS=\"20\" WIDTH=\"40\" HEIGHT=\"12\" WC=\"1.00\""));
+    }
+}
@@ -0,0 +1,348 @@
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::file_processors::ArchiveFormat;
+
+/// Safety limits for untrusted archives
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    /// Maximum total uncompressed size of extracted entries, in bytes
+    pub max_total_size: u64,
+    /// Maximum number of file entries
+    pub max_entries: usize,
+    /// Maximum ratio of extracted bytes to the archive's size on disk
+    pub max_compression_ratio: u64,
+    /// Maximum nesting depth of containers inside containers
+    pub max_depth: usize,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_total_size: 2 * 1024 * 1024 * 1024,
+            max_entries: 10_000,
+            max_compression_ratio: 100,
+            max_depth: 3,
+        }
+    }
+}
+
+/// The compression ratio is only enforced past this many extracted bytes,
+/// so small, highly compressible files (plain text, XML) are not rejected
+pub const RATIO_CHECK_MIN_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Archive safety violations, reported as the archive's (or entry's) error
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("archive has more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("archive expands to more than {0} bytes")]
+    TotalSizeExceeded(u64),
+    #[error("archive compression ratio exceeds {0}:1 (possible zip bomb)")]
+    CompressionRatioExceeded(u64),
+    #[error("nested containers deeper than {0} levels")]
+    NestingTooDeep(usize),
+    #[error("unsafe entry path: {0}")]
+    UnsafePath(String),
+}
+
+/// Bytes extracted from one uploaded file, shared by all the containers nested
+/// in it (archives, email attachments, office document packages), so
+/// `max_total_size` holds for the whole upload rather than per archive
+pub struct ExtractionBudget {
+    limits: ArchiveLimits,
+    used: Cell<u64>,
+    /// First violation, kept for readers that can only return `None`
+    violation: RefCell<Option<ArchiveError>>,
+}
+
+impl ExtractionBudget {
+    pub fn new(limits: ArchiveLimits) -> Self {
+        ExtractionBudget {
+            limits,
+            used: Cell::new(0),
+            violation: RefCell::new(None),
+        }
+    }
+
+    /// Bytes that may still be extracted
+    pub fn remaining(&self) -> u64 {
+        self.limits.max_total_size.saturating_sub(self.used.get())
+    }
+
+    /// Account for `bytes` extracted
+    pub fn consume(&self, bytes: u64) -> Result<(), ArchiveError> {
+        self.used.set(self.used.get().saturating_add(bytes));
+        if self.used.get() > self.limits.max_total_size {
+            return Err(self.violate(ArchiveError::TotalSizeExceeded(self.limits.max_total_size)));
+        }
+        Ok(())
+    }
+
+    /// Fail when `extracted` bytes came out of `compressed` bytes at too high a ratio
+    pub fn check_ratio(&self, extracted: u64, compressed: u64) -> Result<(), ArchiveError> {
+        let ratio = self.limits.max_compression_ratio;
+        if extracted > RATIO_CHECK_MIN_SIZE && extracted / compressed.max(1) > ratio {
+            return Err(self.violate(ArchiveError::CompressionRatioExceeded(ratio)));
+        }
+        Ok(())
+    }
+
+    /// Budget for re-reading parts of a package whose declared size `bytes` was
+    /// already charged (see `check_zip_package`): reads stop at that size
+    /// instead of charging the upload a second time
+    pub fn prepaid(&self, bytes: u64) -> ExtractionBudget {
+        ExtractionBudget::new(ArchiveLimits {
+            max_total_size: bytes,
+            ..self.limits
+        })
+    }
+
+    /// The first violation recorded so far
+    pub fn check(&self) -> Result<(), ArchiveError> {
+        match self.violation.borrow().as_ref() {
+            Some(violation) => Err(violation.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn violate(&self, error: ArchiveError) -> ArchiveError {
+        self.violation.borrow_mut().get_or_insert_with(|| error.clone());
+        error
+    }
+}
+
+/// Read a compressed member (package part, archive entry) into memory within the
+/// budget; `compressed_size` is its size in the container, for the ratio check
+pub fn read_limited(
+    reader: &mut dyn Read,
+    compressed_size: u64,
+    budget: &ExtractionBudget,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // One byte over the remaining budget is enough to detect the overrun
+    let mut data = Vec::new();
+    reader.take(budget.remaining().saturating_add(1)).read_to_end(&mut data)?;
+
+    budget.consume(data.len() as u64)?;
+    budget.check_ratio(data.len() as u64, compressed_size)?;
+    Ok(data)
+}
+
+/// Check the declared sizes of a ZIP-based document read by a library that
+/// extracts it on its own (XLSX, XLSB, ODS through calamine) and charge them to
+/// the budget; returns the declared uncompressed size
+pub fn check_zip_package(path: &Path, budget: &ExtractionBudget) -> Result<u64, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    if archive.len() > budget.limits.max_entries {
+        return Err(ArchiveError::TooManyEntries(budget.limits.max_entries).into());
+    }
+
+    let mut declared = 0u64;
+    for i in 0..archive.len() {
+        declared = declared.saturating_add(archive.by_index_raw(i)?.size());
+    }
+    budget.consume(declared)?;
+    budget.check_ratio(declared, fs::metadata(path)?.len())?;
+    Ok(declared)
+}
+
+/// A regular file inside an archive
+pub struct ArchiveEntry<'a> {
+    /// Entry name as stored in the archive
+    pub name: String,
+    /// Relative path safe to create under a scratch directory
+    /// (`None` for absolute or `../` names)
+    pub safe_path: Option<PathBuf>,
+    /// Uncompressed size declared by the archive
+    pub size: u64,
+    pub reader: &'a mut dyn Read,
+}
+
+/// Call `visit` for every regular file in the archive, in archive order
+pub fn for_each_entry<F>(path: &Path, format: &ArchiveFormat, mut visit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ArchiveEntry<'_>) -> Result<(), Box<dyn Error>>,
+{
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                if file.is_dir() {
+                    continue;
+                }
+
+                let name = file.name().to_string();
+                let safe_path = file.enclosed_name();
+                let size = file.size();
+
+                visit(ArchiveEntry {
+                    name,
+                    safe_path,
+                    size,
+                    reader: &mut file,
+                })?;
+            }
+
+            Ok(())
+        }
+        ArchiveFormat::Tar => walk_tar(tar::Archive::new(fs::File::open(path)?), &mut visit),
+        ArchiveFormat::TarGz => {
+            let decoder = flate2::read::GzDecoder::new(fs::File::open(path)?);
+            walk_tar(tar::Archive::new(decoder), &mut visit)
+        }
+        ArchiveFormat::TarXz => {
+            let decoder = xz2::read::XzDecoder::new(fs::File::open(path)?);
+            walk_tar(tar::Archive::new(decoder), &mut visit)
+        }
+        other => Err(format!("{:?} archives are not supported", other).into()),
+    }
+}
+
+fn walk_tar<R, F>(mut archive: tar::Archive<R>, visit: &mut F) -> Result<(), Box<dyn Error>>
+where
+    R: Read,
+    F: FnMut(ArchiveEntry<'_>) -> Result<(), Box<dyn Error>>,
+{
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        // Skip directories, links and special files
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let safe_path = enclosed_path(&name);
+        let size = entry.size();
+
+        visit(ArchiveEntry {
+            name,
+            safe_path,
+            size,
+            reader: &mut entry,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Relative path of an entry name, rejecting absolute paths and `..` components
+/// (the equivalent of zip's `enclosed_name` for tar)
+fn enclosed_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    if path.as_os_str().is_empty() { None } else { Some(path) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn limits(max_total_size: u64) -> ArchiveLimits {
+        ArchiveLimits {
+            max_total_size,
+            ..ArchiveLimits::default()
+        }
+    }
+
+    #[test]
+    fn enclosed_path_rejects_traversal() {
+        assert_eq!(enclosed_path("docs/./scan.png"), Some(PathBuf::from("docs/scan.png")));
+        assert_eq!(enclosed_path("../etc/passwd"), None);
+        assert_eq!(enclosed_path("docs/../../etc/passwd"), None);
+        assert_eq!(enclosed_path("/etc/passwd"), None);
+        assert_eq!(enclosed_path("."), None);
+    }
+
+    #[test]
+    fn zip_entries_outside_the_archive_have_no_safe_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evil.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        for name in ["ok/scan.txt", "../evil.txt"] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(b"x").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut entries = Vec::new();
+        for_each_entry(&path, &ArchiveFormat::Zip, |entry| {
+            entries.push((entry.name, entry.safe_path));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("ok/scan.txt".to_string(), Some(PathBuf::from("ok/scan.txt"))),
+                ("../evil.txt".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn budget_is_shared_across_containers() {
+        let budget = ExtractionBudget::new(limits(100));
+        assert!(budget.consume(60).is_ok());
+        assert_eq!(budget.remaining(), 40);
+        assert!(budget.check().is_ok());
+
+        assert!(matches!(budget.consume(41), Err(ArchiveError::TotalSizeExceeded(100))));
+        assert_eq!(budget.remaining(), 0);
+        assert!(matches!(budget.check(), Err(ArchiveError::TotalSizeExceeded(100))));
+    }
+
+    #[test]
+    fn ratio_is_checked_past_the_minimum_size() {
+        let budget = ExtractionBudget::new(ArchiveLimits::default());
+        // Tiny but highly compressed files are fine
+        assert!(budget.check_ratio(RATIO_CHECK_MIN_SIZE, 1).is_ok());
+        assert!(budget.check_ratio(RATIO_CHECK_MIN_SIZE + 1, RATIO_CHECK_MIN_SIZE).is_ok());
+        assert!(matches!(
+            budget.check_ratio(RATIO_CHECK_MIN_SIZE + 1, 1024),
+            Err(ArchiveError::CompressionRatioExceeded(100))
+        ));
+        assert!(budget.check().is_err());
+    }
+
+    #[test]
+    fn prepaid_parts_are_not_charged_again() {
+        let budget = ExtractionBudget::new(limits(100));
+        budget.consume(60).unwrap();
+
+        let parts = budget.prepaid(60);
+        assert_eq!(read_limited(&mut Cursor::new(vec![0u8; 60]), 60, &parts).unwrap().len(), 60);
+        assert_eq!(budget.remaining(), 40);
+
+        // Parts that expand past their declared size are cut off
+        assert!(read_limited(&mut Cursor::new(vec![0u8; 1]), 1, &parts).is_err());
+        assert!(parts.check().is_err());
+        assert!(budget.check().is_ok());
+    }
+
+    #[test]
+    fn read_limited_stops_at_the_budget() {
+        let budget = ExtractionBudget::new(limits(10));
+        let data = read_limited(&mut Cursor::new(vec![0u8; 8]), 8, &budget).unwrap();
+        assert_eq!(data.len(), 8);
+
+        let overrun = read_limited(&mut Cursor::new(vec![0u8; 1_000]), 1_000, &budget);
+        assert!(overrun.is_err());
+        assert!(matches!(budget.check(), Err(ArchiveError::TotalSizeExceeded(10))));
+    }
+}
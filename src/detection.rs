@@ -0,0 +1,369 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::file_processors::{ArchiveFormat, FileType, ImageFormat};
+
+/// Bytes read from the start of a file for signature checks; enough for the
+/// tar header (`ustar` at offset 257) and a compressed first tar block
+pub const HEADER_SIZE: usize = 8192;
+
+/// OLE2 files above this size are not scanned for stream names
+const MAX_OLE2_SCAN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// RFC 5322 (and MIME) header fields of an exported email
+const EMAIL_HEADERS: &[&str] = &[
+    "return-path", "received", "from", "sender", "to", "cc", "date", "subject",
+    "message-id", "in-reply-to", "references", "mime-version", "content-type",
+    "delivered-to", "reply-to",
+];
+
+/// Distinct known header fields an email needs, so a text file that happens to
+/// start with "Subject:" or "To:" is not taken for one
+const MIN_EMAIL_HEADERS: usize = 2;
+
+/// Junk some generators put before the PDF header; readers accept it within 1 KiB
+const MAX_PDF_PREFIX: usize = 1024;
+
+/// What the leading bytes say about a file
+enum Signature {
+    Known(FileType),
+    Zip,
+    Ole2,
+    Gzip,
+    Xz,
+    Unknown,
+}
+
+/// File type from content; the extension only breaks ties (which ZIP or OLE2
+/// based format, tar inside gzip) and is used as is when the content is not recognized
+pub fn detect(path: &Path) -> FileType {
+    let claimed = FileType::from_path(path);
+    let Ok(header) = read_header(path) else {
+        return claimed;
+    };
+
+    match signature(&header, &claimed) {
+        Signature::Zip => zip_container_type(path).unwrap_or_else(|| sniff(&header, path)),
+        Signature::Ole2 => ole2_type(path, &claimed),
+        _ => sniff(&header, path),
+    }
+}
+
+/// File type from the first bytes (up to `HEADER_SIZE`) of a file named `path`.
+/// Without the whole file, ZIP and OLE2 based types are taken from the extension
+/// (plain ZIP when it is no help); `detect` refines them once the file is on disk.
+pub fn sniff(header: &[u8], path: &Path) -> FileType {
+    let claimed = FileType::from_path(path);
+
+    match signature(header, &claimed) {
+        Signature::Known(file_type) => file_type,
+        Signature::Zip => match claimed {
+            FileType::Docx
+            | FileType::Pptx
+            | FileType::Xlsx
+            | FileType::Xlsb
+            | FileType::Ods
+            | FileType::Odt => claimed,
+            _ => FileType::Archive(ArchiveFormat::Zip),
+        },
+        Signature::Ole2 => ole2_type_or_claimed(ole2_type_from_bytes(header), &claimed),
+        Signature::Gzip => {
+            let mut decoder = flate2::read::GzDecoder::new(header);
+            if is_tar_header(&mut decoder) || is_claimed_tar(&claimed) {
+                FileType::Archive(ArchiveFormat::TarGz)
+            } else {
+                FileType::Unsupported
+            }
+        }
+        Signature::Xz => {
+            let mut decoder = xz2::read::XzDecoder::new(header);
+            if is_tar_header(&mut decoder) || is_claimed_tar(&claimed) {
+                FileType::Archive(ArchiveFormat::TarXz)
+            } else {
+                FileType::Unsupported
+            }
+        }
+        // Only a missing extension is overridden: notes.txt stays text even if it starts with headers
+        Signature::Unknown if path.extension().is_none() && looks_like_email(header) => FileType::Eml,
+        Signature::Unknown => claimed,
+    }
+}
+
+/// Human-readable claimed type for metadata: the type the extension names,
+/// or the bare extension when it names none
+pub fn claimed_type_label(path: &Path) -> String {
+    match FileType::from_path(path) {
+        FileType::Unsupported => match path.extension() {
+            Some(ext) => format!(".{}", ext.to_string_lossy()),
+            None => "none".to_string(),
+        },
+        claimed => claimed.to_string(),
+    }
+}
+
+fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    fs::File::open(path)?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+fn signature(header: &[u8], claimed: &FileType) -> Signature {
+    let image = |format| Signature::Known(FileType::Image(format));
+
+    match header {
+        [0xFF, 0xD8, 0xFF, ..] => image(ImageFormat::Jpeg),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => image(ImageFormat::Png),
+        [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => image(ImageFormat::Tiff),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => image(ImageFormat::Gif),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => image(ImageFormat::Webp),
+        [b'B', b'M', ..] if header.len() >= 26 => image(ImageFormat::Bmp),
+        [b'P', b'K', 0x03 | 0x05 | 0x07, 0x04 | 0x06 | 0x08, ..] => Signature::Zip,
+        [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, ..] => Signature::Ole2,
+        [0x1F, 0x8B, ..] => Signature::Gzip,
+        [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Signature::Xz,
+        [b'R', b'a', b'r', b'!', 0x1A, 0x07, ..] => {
+            Signature::Known(FileType::Archive(ArchiveFormat::Rar))
+        }
+        [b'{', b'\\', b'r', b't', b'f', ..] => Signature::Known(FileType::Rtf),
+        _ if header.len() > 262 && &header[257..262] == b"ustar" => {
+            Signature::Known(FileType::Archive(ArchiveFormat::Tar))
+        }
+        [b'%', b'P', b'D', b'F', b'-', ..] => Signature::Known(FileType::Pdf),
+        // A "%PDF-" further in is only trusted for files named .pdf; anywhere
+        // else it is as likely to be quoted in a text or mail
+        _ if *claimed == FileType::Pdf
+            && find(&header[..header.len().min(MAX_PDF_PREFIX)], b"%PDF-").is_some() =>
+        {
+            Signature::Known(FileType::Pdf)
+        }
+        _ => Signature::Unknown,
+    }
+}
+
+/// OOXML and ODF packages are ZIP files told apart by their parts; anything
+/// else (including other OPC or ODF flavours) is an ordinary ZIP archive
+fn zip_container_type(path: &Path) -> Option<FileType> {
+    let mut package = zip::ZipArchive::new(fs::File::open(path).ok()?).ok()?;
+
+    if package.index_for_name("[Content_Types].xml").is_some() {
+        let parts = [
+            ("word/document.xml", FileType::Docx),
+            ("ppt/presentation.xml", FileType::Pptx),
+            ("xl/workbook.xml", FileType::Xlsx),
+            ("xl/workbook.bin", FileType::Xlsb),
+        ];
+        if let Some((_, file_type)) = parts.into_iter().find(|(part, _)| package.index_for_name(part).is_some()) {
+            return Some(file_type);
+        }
+    }
+
+    // ODF stores its MIME type uncompressed as the first entry
+    if let Ok(mut mimetype) = package.by_name("mimetype") {
+        let mut mime = String::new();
+        if mimetype.read_to_string(&mut mime).is_ok() {
+            match mime.trim() {
+                "application/vnd.oasis.opendocument.text" => return Some(FileType::Odt),
+                "application/vnd.oasis.opendocument.spreadsheet" => return Some(FileType::Ods),
+                _ => {}
+            }
+        }
+    }
+
+    Some(FileType::Archive(ArchiveFormat::Zip))
+}
+
+/// OLE2 compound files hold XLS, DOC, PPT, MSG and encrypted OOXML; the stream
+/// names in the directory say which
+fn ole2_type(path: &Path, claimed: &FileType) -> FileType {
+    let scannable = fs::metadata(path).is_ok_and(|meta| meta.len() <= MAX_OLE2_SCAN_SIZE);
+    let data = if scannable { fs::read(path).ok() } else { None };
+
+    ole2_type_or_claimed(data.and_then(|data| ole2_type_from_bytes(&data)), claimed)
+}
+
+/// Unrecognized OLE2 files are only read as XLS when named so; a .doc or .msg
+/// would otherwise fail as a broken spreadsheet
+fn ole2_type_or_claimed(detected: Option<FileType>, claimed: &FileType) -> FileType {
+    match detected {
+        Some(file_type) => file_type,
+        None if matches!(claimed, FileType::Xls) => FileType::Xls,
+        None => FileType::Unsupported,
+    }
+}
+
+fn ole2_type_from_bytes(data: &[u8]) -> Option<FileType> {
+    let utf16 = |name: &str| -> Vec<u8> { name.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+
+    // Checked first: DOC and PPT files may embed a workbook object
+    let other = ["WordDocument", "PowerPoint Document", "EncryptedPackage", "__substg1.0_"];
+    if other.iter().any(|name| find(data, &utf16(name)).is_some()) {
+        return Some(FileType::Unsupported);
+    }
+    if find(data, &utf16("Workbook")).is_some() || find(data, &utf16("Book\0")).is_some() {
+        return Some(FileType::Xls);
+    }
+    None
+}
+
+fn is_tar_header(decoder: &mut impl Read) -> bool {
+    let mut block = Vec::with_capacity(512);
+    let _ = decoder.take(512).read_to_end(&mut block);
+    block.len() >= 262 && &block[257..262] == b"ustar"
+}
+
+fn is_claimed_tar(claimed: &FileType) -> bool {
+    matches!(
+        claimed,
+        FileType::Archive(ArchiveFormat::Tar | ArchiveFormat::TarGz | ArchiveFormat::TarXz)
+    )
+}
+
+/// RFC 5322 message: starts with a block of header fields, at least
+/// `MIN_EMAIL_HEADERS` of them distinct known ones
+fn looks_like_email(header: &[u8]) -> bool {
+    let mut known = Vec::new();
+
+    for line in header.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            break;
+        }
+        // Folded continuation of the previous field
+        if line[0] == b' ' || line[0] == b'\t' {
+            continue;
+        }
+
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        let name = &line[..colon];
+        if name.is_empty() || !name.iter().all(|b| b.is_ascii_graphic()) {
+            return false;
+        }
+
+        let name = String::from_utf8_lossy(name).to_lowercase();
+        if EMAIL_HEADERS.contains(&name.as_str()) && !known.contains(&name) {
+            known.push(name);
+            if known.len() >= MIN_EMAIL_HEADERS {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniffed(header: &[u8], name: &str) -> FileType {
+        sniff(header, Path::new(name))
+    }
+
+    fn ole2(stream: &str) -> Vec<u8> {
+        let mut data = vec![0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+        data.resize(512, 0);
+        data.extend(stream.encode_utf16().flat_map(u16::to_le_bytes));
+        data
+    }
+
+    #[test]
+    fn content_wins_over_the_extension() {
+        assert_eq!(sniffed(b"%PDF-1.7\n", "scan.dat"), FileType::Pdf);
+        assert_eq!(sniffed(&[0xFF, 0xD8, 0xFF, 0xE0], "photo.png"), FileType::Image(ImageFormat::Jpeg));
+        assert_eq!(sniffed(b"II*\0\x08\0\0\0", "fax"), FileType::Image(ImageFormat::Tiff));
+        assert_eq!(sniffed(b"{\\rtf1\\ansi}", "letter.doc"), FileType::Rtf);
+        assert_eq!(sniffed(b"hello", "fake.pdf"), FileType::Pdf);
+        assert_eq!(sniffed(b"hello", "notes.txt"), FileType::Unsupported);
+    }
+
+    #[test]
+    fn pdf_header_after_junk_needs_a_pdf_name() {
+        let data = b"\xEF\xBB\xBF\r\n%PDF-1.4\n";
+        assert_eq!(sniffed(data, "scan.pdf"), FileType::Pdf);
+        assert_eq!(sniffed(data, "scan.dat"), FileType::Unsupported);
+        assert_eq!(sniffed(b"See the attached %PDF-1.4 file", "notes.txt"), FileType::Unsupported);
+    }
+
+    #[test]
+    fn zip_and_ole2_types_follow_the_extension() {
+        let zip = b"PK\x03\x04\x14\0";
+        assert_eq!(sniffed(zip, "report.docx"), FileType::Docx);
+        assert_eq!(sniffed(zip, "report.bin"), FileType::Archive(ArchiveFormat::Zip));
+
+        assert_eq!(sniffed(&ole2("Workbook"), "book.dat"), FileType::Xls);
+        assert_eq!(sniffed(&ole2("WordDocument"), "letter.xls"), FileType::Unsupported);
+        // Directory not in the header: only a .xls name makes it a workbook
+        assert_eq!(sniffed(&ole2(""), "book.xls"), FileType::Xls);
+        assert_eq!(sniffed(&ole2(""), "letter.doc"), FileType::Unsupported);
+        assert_eq!(sniffed(&ole2(""), "mail.msg"), FileType::Unsupported);
+    }
+
+    #[test]
+    fn unknown_packages_are_plain_zip_archives() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let package = |name: &str, entries: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            let mut writer = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+            for (entry, content) in entries {
+                writer.start_file(*entry, zip::write::SimpleFileOptions::default()).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+            detect(&path)
+        };
+
+        assert_eq!(package("report.zip", &[("[Content_Types].xml", ""), ("word/document.xml", "")]), FileType::Docx);
+        assert_eq!(package("letter.zip", &[("mimetype", "application/vnd.oasis.opendocument.text")]), FileType::Odt);
+        assert_eq!(
+            package("drawing.vsdx", &[("[Content_Types].xml", ""), ("visio/document.xml", "")]),
+            FileType::Archive(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            package("book.epub", &[("mimetype", "application/epub+zip"), ("a.xhtml", "")]),
+            FileType::Archive(ArchiveFormat::Zip)
+        );
+    }
+
+    #[test]
+    fn emails_need_two_known_headers_and_no_other_extension() {
+        let mail = b"Received: from mx\r\n\tby relay\r\nFrom: a@example.com\r\nX-Spam: no\r\n\r\nbody";
+        assert_eq!(sniffed(mail, "message"), FileType::Eml);
+        assert_eq!(sniffed(mail, "message.txt"), FileType::Unsupported);
+        assert_eq!(sniffed(b"Subject: minutes\n\nbody", "message"), FileType::Unsupported);
+        assert_eq!(sniffed(b"To: me\nTo: you\n\nbody", "message"), FileType::Unsupported);
+        assert_eq!(sniffed(b"Dear Sir,\nFrom: a\nTo: b\n", "message"), FileType::Unsupported);
+    }
+
+    #[test]
+    fn gzip_needs_a_tar_inside_or_a_tar_name() {
+        use std::io::Write;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_cksum();
+        builder.append_data(&mut header, "a.txt", &b"x"[..]).unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let gzip = |data: &[u8]| {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+        assert_eq!(sniffed(&tar, "backup"), FileType::Archive(ArchiveFormat::Tar));
+        assert_eq!(sniffed(&gzip(&tar), "backup.bin"), FileType::Archive(ArchiveFormat::TarGz));
+        assert_eq!(sniffed(&gzip(b"log line"), "server.log.gz"), FileType::Unsupported);
+        assert_eq!(sniffed(&gzip(b"log line"), "backup.tgz"), FileType::Archive(ArchiveFormat::TarGz));
+    }
+}
@@ -1,25 +1,41 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-use calamine::{open_workbook, Data, Reader, Xlsx};
-use docx_rs::read_docx;
+use calamine::{open_workbook, Data, Dimensions, Reader, Sheets};
+use mail_parser::{Address, MessageParser, MessagePart, MimeHeaders};
 
-use crate::ocr_engine::OcrEngine;
+use crate::archive::{self, ArchiveError, ArchiveLimits, ExtractionBudget};
+use crate::detection;
+use crate::image_frames;
+use crate::ocr_engine::{OcrAnalysisResult, OcrEngine};
+use crate::office_xml::{self, TextBoxes};
+use crate::rtf::rtf_to_text;
+use crate::utils::extract_metadata;
 
 /// Supported file types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum FileType {
     Image(ImageFormat),
     Pdf,
     Docx,
+    Pptx,
+    Odt,
+    Rtf,
+    Eml,
     Xlsx,
     Xls,
+    Xlsb,
+    Ods,
     Archive(ArchiveFormat),
+    #[default]
     Unsupported,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ImageFormat {
     Jpeg,
     Png,
@@ -29,42 +45,186 @@ pub enum ImageFormat {
     Webp,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ArchiveFormat {
     Zip,
     Tar,
+    TarGz,
+    TarXz,
     Rar,
 }
 
+/// Optional DOCX parts appended after the body text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocxPart {
+    Headers,
+    Footers,
+    Footnotes,
+    Endnotes,
+    Comments,
+    TextBoxes,
+}
+
+impl DocxPart {
+    pub const ALL: [DocxPart; 6] = [
+        DocxPart::Headers,
+        DocxPart::Footers,
+        DocxPart::Footnotes,
+        DocxPart::Endnotes,
+        DocxPart::Comments,
+        DocxPart::TextBoxes,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            DocxPart::Headers => "Headers",
+            DocxPart::Footers => "Footers",
+            DocxPart::Footnotes => "Footnotes",
+            DocxPart::Endnotes => "Endnotes",
+            DocxPart::Comments => "Comments",
+            DocxPart::TextBoxes => "Text boxes",
+        }
+    }
+}
+
+/// How the text of a page was obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionMethod {
+    /// Text layer of the document (PDF text, DOCX runs, spreadsheet cells)
+    DirectText,
+    /// Tesseract OCR
+    Ocr,
+}
+
+impl ExtractionMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtractionMethod::DirectText => "direct_text",
+            ExtractionMethod::Ocr => "ocr",
+        }
+    }
+}
+
+/// Text of a single page (or spreadsheet sheet)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PageResult {
+    /// 1-based page number, used for citations
+    pub page_number: usize,
+    /// Sheet name for spreadsheets, media name for embedded images
+    pub label: Option<String>,
+    pub text: String,
+    pub char_count: usize,
+    /// Average OCR word confidence (0-100), if the page was OCR'd
+    pub confidence: Option<f32>,
+    /// Language detected in the OCR text (ISO 639-3)
+    pub language: Option<String>,
+    pub method: ExtractionMethod,
+    /// Word-level OCR output (layout, confidences) from the same Tesseract pass
+    #[serde(skip)]
+    pub ocr: Option<OcrAnalysisResult>,
+    /// Cell grid of a spreadsheet sheet, for CSV export
+    #[serde(skip)]
+    pub table: Option<Vec<Vec<String>>>,
+}
+
+impl PageResult {
+    pub fn new(page_number: usize, text: String, method: ExtractionMethod) -> Self {
+        PageResult {
+            page_number,
+            label: None,
+            char_count: text.chars().count(),
+            text,
+            confidence: None,
+            language: None,
+            method,
+            ocr: None,
+            table: None,
+        }
+    }
+
+    /// Page recognized by Tesseract
+    pub fn from_ocr(page_number: usize, analysis: OcrAnalysisResult) -> Self {
+        let mut page = PageResult::new(page_number, analysis.text.clone(), ExtractionMethod::Ocr);
+        page.confidence = Some(analysis.avg_confidence);
+        page.language = analysis.detected_language.clone();
+        page.ocr = Some(analysis);
+        page
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+}
+
 /// Processing result for a single file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ProcessResult {
     pub file_type: FileType,
     pub page_count: usize,
     pub text: String,
+    pub pages: Vec<PageResult>,
+    /// Path inside a container, e.g. `inner/path.pdf` or `nested.zip!/scan.png`
+    pub entry_name: Option<String>,
+    /// Per-entry failure inside a container (the container itself still succeeded)
+    pub error: Option<String>,
+    /// Extra metadata collected while the entry was still on disk
+    pub metadata: HashMap<String, String>,
+}
+
+impl ProcessResult {
+    fn failed(file_type: FileType, entry_name: &str, error: String) -> Self {
+        ProcessResult {
+            file_type,
+            entry_name: Some(entry_name.to_string()),
+            error: Some(error),
+            ..Default::default()
+        }
+    }
 }
 
 /// Main file processor
 pub struct FileProcessor {
     use_pdf_ocr: bool,
+    spreadsheet_formulas: bool,
+    archive_limits: ArchiveLimits,
+    docx_parts: Vec<DocxPart>,
+    ocr_embedded_images: bool,
 }
 
-fn extract_text_from_paragraph(p: &docx_rs::Paragraph, text: &mut String) {
-    for p_child in &p.children {
-        if let docx_rs::ParagraphChild::Run(r) = p_child {
-            for r_child in &r.children {
-                if let docx_rs::RunChild::Text(t) = r_child {
-                    text.push_str(&t.text);
-                    text.push(' ');
-                }
-            }
+impl FileProcessor {
+    pub fn new(use_pdf_ocr: bool) -> Self {
+        FileProcessor {
+            use_pdf_ocr,
+            spreadsheet_formulas: false,
+            archive_limits: ArchiveLimits::default(),
+            docx_parts: DocxPart::ALL.to_vec(),
+            ocr_embedded_images: false,
         }
     }
-}
 
-impl FileProcessor {
-    pub fn new(use_pdf_ocr: bool) -> Self {
-        FileProcessor { use_pdf_ocr }
+    /// Output formula text (`=SUM(A1:A3)`) instead of cached formula results
+    pub fn with_spreadsheet_formulas(mut self, formulas: bool) -> Self {
+        self.spreadsheet_formulas = formulas;
+        self
+    }
+
+    pub fn with_archive_limits(mut self, limits: ArchiveLimits) -> Self {
+        self.archive_limits = limits;
+        self
+    }
+
+    /// DOCX parts extracted in addition to the body (all by default)
+    pub fn with_docx_parts(mut self, parts: &[DocxPart]) -> Self {
+        self.docx_parts = parts.to_vec();
+        self
+    }
+
+    /// OCR images embedded in office documents (scans pasted into DOCX, slide pictures)
+    pub fn with_embedded_image_ocr(mut self, enabled: bool) -> Self {
+        self.ocr_embedded_images = enabled;
+        self
     }
 
     pub fn process_file(
@@ -72,29 +232,91 @@ impl FileProcessor {
         path: &Path,
         ocr_engine: &OcrEngine,
     ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
-        let file_type = FileType::from_path(path);
+        // One budget for everything extracted from this file, however deeply nested
+        let budget = ExtractionBudget::new(self.archive_limits);
+        let mut results = self.process_file_at_depth(path, ocr_engine, 0, &budget)?;
+
+        for result in results.iter_mut().filter(|r| r.entry_name.is_none()) {
+            let mut metadata = extract_metadata(path, &result.file_type, &budget);
+            metadata.extend(std::mem::take(&mut result.metadata));
+            result.metadata = metadata;
+        }
+
+        Ok(results)
+    }
+
+    /// `depth` counts the containers (archives) this file was extracted from
+    fn process_file_at_depth(
+        &self,
+        path: &Path,
+        ocr_engine: &OcrEngine,
+        depth: usize,
+        budget: &ExtractionBudget,
+    ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let file_type = FileType::detect(path);
 
-        match file_type {
-            FileType::Image(_) => self.process_image(path, ocr_engine),
+        let mut results = match &file_type {
+            FileType::Image(_) => self.process_image(path, &file_type, ocr_engine),
             FileType::Pdf => self.process_pdf(path, ocr_engine),
-            FileType::Docx => self.process_docx(path),
-            FileType::Xlsx | FileType::Xls => self.process_excel(path),
-            FileType::Archive(_) => self.process_archive(path, ocr_engine),
+            FileType::Docx => self.process_docx(path, ocr_engine, budget),
+            FileType::Pptx => self.process_pptx(path, ocr_engine, budget),
+            FileType::Odt => self.process_odt(path, budget),
+            FileType::Rtf => self.process_rtf(path),
+            FileType::Eml => self.process_eml(path, ocr_engine, depth, budget),
+            FileType::Xlsx | FileType::Xls | FileType::Xlsb | FileType::Ods => {
+                self.process_excel(path, &file_type, budget)
+            }
+            FileType::Archive(format) => self.process_archive(path, format, ocr_engine, depth, budget),
             FileType::Unsupported => Err("Unsupported file format".into()),
+        }?;
+
+        // Results of nested entries were tagged against their own names
+        for result in results.iter_mut().filter(|r| r.entry_name.is_none()) {
+            result.metadata.extend(type_mismatch(path, &file_type));
         }
+
+        Ok(results)
     }
 
     fn process_image(
         &self,
         path: &Path,
+        file_type: &FileType,
         ocr_engine: &OcrEngine,
     ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
-        let text = ocr_engine.extract_text_from_image(path)?; // повертає String
+        // Fax TIFFs hold many pages (IFDs); animated GIFs many frames
+        let frames = match file_type {
+            FileType::Image(format) => image_frames::split_frames(path, format)?,
+            _ => return Err("Not an image".into()),
+        };
+
+        let analyses = if frames.undecoded() {
+            ocr_engine.extract_pages(path)?
+        } else {
+            frames
+                .frames()
+                .iter()
+                .map(|frame| ocr_engine.extract_with_confidence(frame))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let pages: Vec<PageResult> = analyses
+            .into_iter()
+            .enumerate()
+            .map(|(i, analysis)| PageResult::from_ocr(i + 1, analysis))
+            .collect();
+
+        let text = pages
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
 
         Ok(vec![ProcessResult {
-            file_type: FileType::from_path(path),
-            page_count: 1,
+            file_type: file_type.clone(),
+            page_count: pages.len(),
             text,
+            pages,
+            ..Default::default()
         }])
     }
 
@@ -107,144 +329,1086 @@ impl FileProcessor {
 
         let pdf_data = fs::read(path)?;
 
-        // Try to extract text directly from PDF
-        let direct_text = match pdf_extract::extract_text_from_mem(&pdf_data) {
-            Ok(text) if !text.trim().is_empty() && text.len() > 100 => Some(text),
-            _ => None,
-        };
-
-        let text = if let Some(text) = direct_text {
-            text
-        } else if self.use_pdf_ocr {
-            // Fall back to OCR
-            self.extract_text_from_pdf_with_ocr(path, ocr_engine)?
-        } else {
-            String::new()
-        };
-
         // Get page count
         let page_count = FileOptions::cached()
             .load(pdf_data.as_slice())
             .map(|pdf| pdf.num_pages() as usize)
             .unwrap_or(1);
 
+        // Direct text per page; an unreadable text layer is treated as empty pages
+        let mut direct_pages = pdf_extract::extract_text_from_mem_by_pages(&pdf_data)
+            .unwrap_or_default();
+        let page_count = page_count.max(direct_pages.len());
+        direct_pages.resize(page_count, String::new());
+
+        // Decide per page: mixed PDFs often have digital pages followed by scans
+        let ocr_page_numbers: Vec<usize> = if self.use_pdf_ocr {
+            direct_pages
+                .iter()
+                .enumerate()
+                .filter(|(_, text)| page_needs_ocr(text))
+                .map(|(i, _)| i + 1)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut ocr_pages = if ocr_page_numbers.is_empty() {
+            HashMap::new()
+        } else {
+            self.extract_text_from_pdf_with_ocr(path, &ocr_page_numbers, ocr_engine)
+        };
+
+        // A page that cannot be OCR'd keeps its direct text; the others are unaffected
+        let mut ocr_errors = Vec::new();
+        let pages: Vec<PageResult> = direct_pages
+            .into_iter()
+            .enumerate()
+            .map(|(i, direct_text)| match ocr_pages.remove(&(i + 1)) {
+                Some(Ok(page)) => page,
+                Some(Err(e)) => {
+                    log::warn!("{}: OCR failed on page {}: {}", path.display(), i + 1, e);
+                    ocr_errors.push(format!("page {}: {}", i + 1, e));
+                    PageResult::new(i + 1, direct_text, ExtractionMethod::DirectText)
+                }
+                None => PageResult::new(i + 1, direct_text, ExtractionMethod::DirectText),
+            })
+            .collect();
+
+        let mut metadata = HashMap::new();
+        if !ocr_errors.is_empty() {
+            metadata.insert("ocr_errors".to_string(), ocr_errors.join("; "));
+        }
+
+        let text = pages
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
         Ok(vec![ProcessResult {
             file_type: FileType::Pdf,
             page_count,
             text,
+            pages,
+            metadata,
+            ..Default::default()
         }])
     }
 
+    /// OCR the given (1-based) PDF pages one by one, keyed by page number;
+    /// each page carries its own rasterization or Tesseract error
     fn extract_text_from_pdf_with_ocr(
         &self,
-        _path: &Path,
-        _ocr_engine: &OcrEngine,
-    ) -> Result<String, Box<dyn Error>> {
-        // PDF image extraction is complex in pdf 0.8
-        // For now, return empty string - can be implemented later
-        Ok(String::from("(OCR from PDF images not yet implemented)"))
+        path: &Path,
+        page_numbers: &[usize],
+        ocr_engine: &OcrEngine,
+    ) -> HashMap<usize, Result<PageResult, String>> {
+        let dir = pdf_raster_dir();
+
+        page_numbers
+            .iter()
+            .map(|&page_number| {
+                let page = dir
+                    .as_ref()
+                    .map_err(|e| e.to_string())
+                    .and_then(|dir| {
+                        let image_path = rasterize_pdf_page(path, page_number, ocr_engine.dpi(), dir.path())
+                            .map_err(|e| e.to_string())?;
+                        let analysis = ocr_engine.extract_with_confidence(&image_path)
+                            .map_err(|e| e.to_string())?;
+                        Ok(PageResult::from_ocr(page_number, analysis))
+                    });
+                (page_number, page)
+            })
+            .collect()
     }
 
-    fn process_docx(&self, path: &Path) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
-        let docx_data = fs::read(path)?;
-        let docx = read_docx(&docx_data)
+    fn process_docx(
+        &self,
+        path: &Path,
+        ocr_engine: &OcrEngine,
+        budget: &ExtractionBudget,
+    ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let mut package = zip::ZipArchive::new(fs::File::open(path)?)
             .map_err(|e| format!("Failed to parse DOCX: {}", e))?;
+        let document = office_xml::read_package_part(&mut package, "word/document.xml", budget);
+        budget.check()?;
+        let document = document.ok_or("Failed to parse DOCX: word/document.xml is missing")?;
+
+        // Body text; text boxes are listed in their own section when requested
+        let mut text = office_xml::wordml_text(&document, TextBoxes::Skip)
+            .map_err(|e| format!("Failed to parse DOCX: {}", e))?;
+
+        for part in &self.docx_parts {
+            let part_texts = match part {
+                DocxPart::TextBoxes => vec![office_xml::wordml_text(&document, TextBoxes::Only)?],
+                _ => docx_part_names(&package, *part)
+                    .iter()
+                    .filter_map(|name| office_xml::read_package_part(&mut package, name, budget))
+                    .map(|xml| office_xml::wordml_text(&xml, TextBoxes::Include))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+
+            // Sections usually repeat the same header/footer
+            let mut section = String::new();
+            let mut seen = Vec::new();
+            for part_text in part_texts {
+                let part_text = part_text.trim().to_string();
+                if part_text.is_empty() || seen.contains(&part_text) {
+                    continue;
+                }
+                section.push_str(&part_text);
+                section.push('\n');
+                seen.push(part_text);
+            }
+
+            if !section.is_empty() {
+                text.push_str(&format!("\n=== {} ===\n", part.title()));
+                text.push_str(&section);
+            }
+        }
+
+        // Page count saved by Word (docProps/app.xml); estimate (500 words per page) without it
+        let mut metadata = HashMap::new();
+        let page_count = match office_xml::document_properties(path, budget)
+            .get("pages")
+            .and_then(|pages| pages.parse::<usize>().ok())
+            .filter(|&pages| pages > 0)
+        {
+            Some(pages) => pages,
+            None => {
+                metadata.insert("page_count_estimated".to_string(), "true".to_string());
+                estimate_page_count(&text)
+            }
+        };
+
+        let mut images = Vec::new();
+        if self.ocr_embedded_images {
+            let media = docx_image_names(&mut package, &document, budget);
+            images = ocr_package_images(&mut package, &media, ocr_engine, budget)?;
+
+            // The OCR'd text lives in the image results; the body only points to them
+            if !images.is_empty() {
+                text.push_str("\n=== Embedded images (OCR) ===\n");
+                for image in &images {
+                    text.push_str(&embedded_image_reference(image));
+                }
+            }
+            metadata.insert("ocr_embedded_images".to_string(), images.len().to_string());
+        }
+        // Parts skipped over the budget would otherwise go unnoticed
+        budget.check()?;
+
+        // DOCX has no stored page boundaries, so the body is one record; the images
+        // follow as their own results (report.docx!/word/media/image1.png)
+        let mut results = vec![ProcessResult {
+            file_type: FileType::Docx,
+            page_count,
+            pages: vec![PageResult::new(1, text.clone(), ExtractionMethod::DirectText)],
+            text,
+            metadata,
+            ..Default::default()
+        }];
+        results.extend(images);
+
+        Ok(results)
+    }
+
+    fn process_pptx(
+        &self,
+        path: &Path,
+        ocr_engine: &OcrEngine,
+        budget: &ExtractionBudget,
+    ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let mut package = zip::ZipArchive::new(fs::File::open(path)?)
+            .map_err(|e| format!("Failed to parse PPTX: {}", e))?;
+        let presentation = office_xml::read_package_part(&mut package, "ppt/presentation.xml", budget);
+        budget.check()?;
+        let presentation = presentation.ok_or("Failed to parse PPTX: ppt/presentation.xml is missing")?;
+
+        // Slide order is the order of p:sldIdLst, not of the slideN.xml names
+        let presentation_rels = office_xml::relationships(&mut package, "ppt/presentation.xml", budget);
+        let slide_parts: Vec<String> = office_xml::slide_references(&presentation)
+            .map_err(|e| format!("Failed to parse PPTX: {}", e))?
+            .iter()
+            .filter_map(|id| presentation_rels.get(id))
+            .map(|relationship| relationship.target.clone())
+            .collect();
 
-        // Extract text from paragraphs
         let mut text = String::new();
-        let document = docx.document;
+        let mut pages = Vec::new();
+        // Pictures reused across slides (logos, backgrounds) are OCR'd once
+        let mut images: Vec<ProcessResult> = Vec::new();
+        let mut seen_media = HashSet::new();
 
-        for child in &document.children {
-            // Extract text from paragraphs
-            if let docx_rs::DocumentChild::Paragraph(p) = child {
-                extract_text_from_paragraph(&p, &mut text);
-                text.push('\n');
+        for (i, slide_part) in slide_parts.iter().enumerate() {
+            let slide_number = i + 1;
+            let slide_xml = office_xml::read_package_part(&mut package, slide_part, budget).unwrap_or_default();
+            let slide_rels = office_xml::relationships(&mut package, slide_part, budget);
+
+            let shapes = office_xml::slide_shapes(&slide_xml)?;
+            let title = shapes
+                .iter()
+                .find(|shape| shape.is_title())
+                .map(|shape| shape.text.trim().replace('\n', " "));
+
+            let mut slide_text = String::new();
+            for shape in shapes.iter().filter(|shape| !shape.is_boilerplate()) {
+                slide_text.push_str(shape.text.trim_end());
+                slide_text.push('\n');
             }
 
-            // Extract text from tables
-            if let docx_rs::DocumentChild::Table(tbl) = child {
-                for table_child in &tbl.rows {
-                    let docx_rs::TableChild::TableRow(row) = table_child;
+            // Speaker notes are the body placeholder of the linked notes slide
+            let notes_part = slide_rels
+                .values()
+                .find(|relationship| relationship.kind == "notesSlide")
+                .map(|relationship| relationship.target.clone());
+            if let Some(notes_xml) = notes_part.and_then(|part| office_xml::read_package_part(&mut package, &part, budget)) {
+                let notes: Vec<String> = office_xml::slide_shapes(&notes_xml)?
+                    .into_iter()
+                    .filter(|shape| shape.placeholder.as_deref() == Some("body"))
+                    .map(|shape| shape.text.trim().to_string())
+                    .collect();
+                if !notes.is_empty() {
+                    slide_text.push_str(&format!("--- Notes ---\n{}\n", notes.join("\n")));
+                }
+            }
 
-                    for cell_child in &row.cells {
-                        let docx_rs::TableRowChild::TableCell(cell) = cell_child;
+            if self.ocr_embedded_images {
+                let media: Vec<String> = office_xml::image_references(&slide_xml)?
+                    .iter()
+                    .filter_map(|id| slide_rels.get(id))
+                    .map(|relationship| relationship.target.clone())
+                    .collect();
+                let new_media: Vec<String> = media
+                    .iter()
+                    .filter(|name| seen_media.insert(name.to_string()))
+                    .cloned()
+                    .collect();
+                images.extend(ocr_package_images(&mut package, &new_media, ocr_engine, budget)?);
 
-                        for cell_content in &cell.children {
-                            if let docx_rs::TableCellContent::Paragraph(p) = cell_content {
-                                extract_text_from_paragraph(&p, &mut text);
-                            }
-                        }
-                        text.push('\t'); // Tab separator for cells
+                // The OCR'd text lives in the image results; the slide only points to them
+                for name in &media {
+                    if let Some(image) = images.iter().find(|image| image.entry_name.as_ref() == Some(name)) {
+                        slide_text.push_str(&embedded_image_reference(image));
                     }
-                    text.push('\n'); // New line for each row
                 }
             }
+
+            let page = PageResult::new(slide_number, slide_text, ExtractionMethod::DirectText);
+
+            let label = title.unwrap_or_else(|| format!("Slide {}", slide_number));
+            text.push_str(&format!("\n=== Slide {}: {} ===\n", slide_number, label));
+            text.push_str(&page.text);
+            pages.push(page.with_label(&label));
         }
+        budget.check()?;
 
-        // Estimate page count (approximate: 500 words per page)
-        let word_count = text.split_whitespace().count();
-        let page_count = (word_count as f32 / 500.0).ceil() as usize;
+        let mut metadata = HashMap::new();
+        if self.ocr_embedded_images {
+            metadata.insert("ocr_embedded_images".to_string(), images.len().to_string());
+        }
+
+        // Like DOCX: the images follow as their own results (deck.pptx!/ppt/media/image1.png)
+        let mut results = vec![ProcessResult {
+            file_type: FileType::Pptx,
+            page_count: slide_parts.len().max(1),
+            text,
+            pages,
+            metadata,
+            ..Default::default()
+        }];
+        results.extend(images);
+
+        Ok(results)
+    }
+
+    fn process_odt(&self, path: &Path, budget: &ExtractionBudget) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let mut package = zip::ZipArchive::new(fs::File::open(path)?)
+            .map_err(|e| format!("Failed to parse ODT: {}", e))?;
+        let content = office_xml::read_package_part(&mut package, "content.xml", budget);
+        budget.check()?;
+        let content = content.ok_or("Failed to parse ODT: content.xml is missing")?;
+        let text = office_xml::odf_text(&content)
+            .map_err(|e| format!("Failed to parse ODT: {}", e))?;
+
+        // Page count saved by the editor (meta.xml); estimate without it
+        let mut metadata = HashMap::new();
+        let page_count = match office_xml::read_package_part(&mut package, "meta.xml", budget)
+            .and_then(|meta| office_xml::odf_page_count(&meta))
+            .filter(|&pages| pages > 0)
+        {
+            Some(pages) => pages,
+            None => {
+                metadata.insert("page_count_estimated".to_string(), "true".to_string());
+                estimate_page_count(&text)
+            }
+        };
 
         Ok(vec![ProcessResult {
-            file_type: FileType::Docx,
-            page_count: page_count.max(1),
+            file_type: FileType::Odt,
+            page_count,
+            pages: vec![PageResult::new(1, text.clone(), ExtractionMethod::DirectText)],
             text,
+            metadata,
+            ..Default::default()
         }])
     }
 
-    fn process_excel(&self, path: &Path) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
-        let mut workbook: Xlsx<_> = open_workbook(path)?;
+    fn process_rtf(&self, path: &Path) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let text = rtf_to_text(&fs::read(path)?)
+            .map_err(|e| format!("Failed to parse RTF: {}", e))?;
+
+        // RTF stores no page count
+        let mut metadata = HashMap::new();
+        metadata.insert("page_count_estimated".to_string(), "true".to_string());
+
+        Ok(vec![ProcessResult {
+            file_type: FileType::Rtf,
+            page_count: estimate_page_count(&text),
+            pages: vec![PageResult::new(1, text.clone(), ExtractionMethod::DirectText)],
+            text,
+            metadata,
+            ..Default::default()
+        }])
+    }
+
+    fn process_excel(
+        &self,
+        path: &Path,
+        file_type: &FileType,
+        budget: &ExtractionBudget,
+    ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        // calamine extracts the ZIP-based formats itself, so their sizes are checked up front
+        let declared = if matches!(file_type, FileType::Xlsx | FileType::Xlsb | FileType::Ods) {
+            archive::check_zip_package(path, budget)?
+        } else {
+            0
+        };
+
+        // Pick the reader by detected type; .xls is BIFF, not OOXML
+        let mut workbook: Sheets<_> = match file_type {
+            FileType::Xls => Sheets::Xls(open_workbook(path)?),
+            FileType::Xlsb => Sheets::Xlsb(open_workbook(path)?),
+            FileType::Ods => Sheets::Ods(open_workbook(path)?),
+            _ => Sheets::Xlsx(open_workbook(path)?),
+        };
+        // calamine only tells dates apart; percentages and decimals need the cell styles.
+        // The parts were charged with the declared package size above.
+        let number_formats = match file_type {
+            FileType::Xlsx => {
+                let parts_budget = budget.prepaid(declared);
+                let formats = zip::ZipArchive::new(fs::File::open(path)?)
+                    .map(|mut package| office_xml::xlsx_number_formats(&mut package, &parts_budget))
+                    .unwrap_or_default();
+                parts_budget.check()?;
+                formats
+            }
+            _ => HashMap::new(),
+        };
+
         let mut text = String::new();
+        let mut pages = Vec::new();
 
         // Get sheet names
         let sheet_names = workbook.sheet_names().to_vec();
 
-        for sheet_name in &sheet_names {
+        for (i, sheet_name) in sheet_names.iter().enumerate() {
             text.push_str(&format!("\n=== Sheet: {} ===\n", sheet_name));
 
-            if let Ok(range) = workbook.worksheet_range(sheet_name) {
-                for row in range.rows() {
-                    for cell in row.iter() {
-                        // Convert cell to string
-                        let cell_text = match cell {
-                            Data::String(s) => s.to_string(),
-                            Data::Float(f) => f.to_string(),
-                            Data::Int(i) => i.to_string(),
-                            Data::Bool(b) => b.to_string(),
-                            Data::DateTime(dt) => format!("{:?}", dt),
-                            Data::DateTimeIso(s) => s.clone(),
-                            Data::DurationIso(s) => s.clone(),
-                            Data::Error(e) => format!("[Error: {:?}]", e),
-                            Data::Empty => String::new(),
-                        };
-
-                        text.push_str(&cell_text);
-                        text.push('\t'); // Tab separator
-                    }
-                    text.push('\n'); // New line for each row
+            let grid = self.sheet_grid(&mut workbook, sheet_name, number_formats.get(sheet_name));
+            let mut sheet_text = String::new();
+            for row in &grid {
+                for cell_text in row {
+                    sheet_text.push_str(cell_text);
+                    sheet_text.push('\t'); // Tab separator
                 }
+                sheet_text.push('\n'); // New line for each row
             }
+
+            text.push_str(&sheet_text);
+            let mut page = PageResult::new(i + 1, sheet_text, ExtractionMethod::DirectText)
+                .with_label(sheet_name);
+            page.table = Some(grid);
+            pages.push(page);
         }
 
         Ok(vec![ProcessResult {
-            file_type: FileType::from_path(path),
+            file_type: file_type.clone(),
             page_count: sheet_names.len().max(1),
             text,
+            pages,
+            ..Default::default()
         }])
     }
 
+    /// Sheet as a grid of formatted cells: merged regions repeat their value,
+    /// formulas are shown as `=FORMULA` when enabled. `number_formats` holds the
+    /// format codes of the sheet's cells by absolute position, when known.
+    fn sheet_grid<RS: io::Read + io::Seek>(
+        &self,
+        workbook: &mut Sheets<RS>,
+        sheet_name: &str,
+        number_formats: Option<&HashMap<(u32, u32), String>>,
+    ) -> Vec<Vec<String>> {
+        let Ok(range) = workbook.worksheet_range(sheet_name) else {
+            return Vec::new();
+        };
+        let (Some(origin), Some(last)) = (range.start(), range.end()) else {
+            return Vec::new();
+        };
+
+        let mut grid: Vec<Vec<String>> = range
+            .rows()
+            .enumerate()
+            .map(|(row, cells)| {
+                cells
+                    .iter()
+                    .enumerate()
+                    .map(|(col, cell)| {
+                        let position = (origin.0 + row as u32, origin.1 + col as u32);
+                        let format = number_formats.and_then(|formats| formats.get(&position));
+                        format_cell(cell, format.map(String::as_str))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if self.spreadsheet_formulas
+            && let Ok(formulas) = workbook.worksheet_formula(sheet_name)
+            && let Some(formula_start) = formulas.start()
+        {
+            for (row, col, formula) in formulas.used_cells() {
+                if !formula.is_empty() {
+                    let position = (formula_start.0 + row as u32, formula_start.1 + col as u32);
+                    set_grid_cell(&mut grid, origin, position, format!("={}", formula));
+                }
+            }
+        }
+
+        // Only the top-left cell of a merged region stores the value
+        let merged = match workbook {
+            Sheets::Xlsx(xlsx) => xlsx.worksheet_merge_cells(sheet_name).and_then(|r| r.ok()),
+            Sheets::Xls(xls) => xls.worksheet_merge_cells(sheet_name),
+            _ => None,
+        };
+
+        fill_merged_regions(&mut grid, origin, last, &merged.unwrap_or_default());
+
+        grid
+    }
+
     fn process_archive(
         &self,
-        _path: &Path,
-        _ocr_engine: &OcrEngine,
+        path: &Path,
+        format: &ArchiveFormat,
+        ocr_engine: &OcrEngine,
+        depth: usize,
+        budget: &ExtractionBudget,
+    ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+        let limits = self.archive_limits;
+        if depth >= limits.max_depth {
+            return Err(ArchiveError::NestingTooDeep(limits.max_depth).into());
+        }
+
+        let archive_size = fs::metadata(path)?.len();
+
+        // Entries are extracted one at a time and removed right after processing
+        let scratch = tempfile::Builder::new().prefix("ocr_archive_").tempdir()?;
+        let mut results = Vec::new();
+        let mut entry_count = 0;
+        // Bytes extracted from this archive, for its compression ratio
+        let mut extracted: u64 = 0;
+
+        let walked = archive::for_each_entry(path, format, |entry| {
+            entry_count += 1;
+            if entry_count > limits.max_entries {
+                return Err(ArchiveError::TooManyEntries(limits.max_entries).into());
+            }
+
+            let Some(relative) = entry.safe_path else {
+                results.push(ProcessResult::failed(
+                    FileType::from_path(Path::new(&entry.name)),
+                    &entry.name,
+                    ArchiveError::UnsafePath(entry.name.clone()).to_string(),
+                ));
+                return Ok(());
+            };
+
+            // Declared sizes can lie, so the budget is enforced on the bytes actually written
+            let remaining = budget.remaining();
+            if entry.size > remaining {
+                return Err(ArchiveError::TotalSizeExceeded(limits.max_total_size).into());
+            }
+
+            // Entries are recognized by content, so only their first bytes are read up front
+            let mut header = Vec::new();
+            (&mut *entry.reader).take(detection::HEADER_SIZE as u64).read_to_end(&mut header)?;
+            if matches!(detection::sniff(&header, &relative), FileType::Unsupported) {
+                return Ok(());
+            }
+
+            let entry_path = scratch.path().join(&relative);
+            if let Some(parent) = entry_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut entry_file = fs::File::create(&entry_path)?;
+            entry_file.write_all(&header)?;
+            let written = header.len() as u64
+                + io::copy(
+                    &mut entry.reader.take((remaining + 1).saturating_sub(header.len() as u64)),
+                    &mut entry_file,
+                )?;
+            drop(entry_file);
+
+            extracted += written;
+            budget.consume(written)?;
+            budget.check_ratio(extracted, archive_size)?;
+
+            results.extend(self.process_entry(&entry_path, &entry.name, ocr_engine, depth, budget));
+            let _ = fs::remove_file(&entry_path);
+            Ok(())
+        });
+
+        // A limit hit midway keeps the entries already processed; the archive
+        // itself reports why the rest is missing
+        if let Err(e) = walked {
+            if results.is_empty() {
+                return Err(e);
+            }
+            results.push(ProcessResult {
+                file_type: FileType::Archive(format.clone()),
+                error: Some(e.to_string()),
+                ..Default::default()
+            });
+        }
+
+        if results.is_empty() {
+            return Err("No supported files in archive".into());
+        }
+
+        Ok(results)
+    }
+
+    /// Process a file extracted from a container (archive entry, email attachment)
+    /// as a child result named after `entry_name`; failures become per-entry errors
+    fn process_entry(
+        &self,
+        entry_path: &Path,
+        entry_name: &str,
+        ocr_engine: &OcrEngine,
+        depth: usize,
+        budget: &ExtractionBudget,
+    ) -> Vec<ProcessResult> {
+        match self.process_file_at_depth(entry_path, ocr_engine, depth + 1, budget) {
+            Ok(entry_results) => entry_results
+                .into_iter()
+                .map(|mut result| {
+                    if result.entry_name.is_none() {
+                        let mut metadata = extract_metadata(entry_path, &result.file_type, budget);
+                        metadata.remove("path");
+                        metadata.extend(result.metadata);
+                        result.metadata = metadata;
+                    }
+
+                    // Nested containers: outer.zip!/inner.zip!/scan.png
+                    result.entry_name = Some(match result.entry_name {
+                        Some(inner) => format!("{}!/{}", entry_name, inner),
+                        None => entry_name.to_string(),
+                    });
+                    result
+                })
+                .collect(),
+            Err(e) => {
+                let file_type = FileType::detect(entry_path);
+                let mut result = ProcessResult::failed(file_type.clone(), entry_name, e.to_string());
+                result.metadata = type_mismatch(entry_path, &file_type);
+                vec![result]
+            }
+        }
+    }
+
+    fn process_eml(
+        &self,
+        path: &Path,
+        ocr_engine: &OcrEngine,
+        depth: usize,
+        budget: &ExtractionBudget,
     ) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
-        // Placeholder for archive processing
-        // Would extract and process contained files
-        Err("Archive processing not implemented in this version".into())
+        let raw = fs::read(path)?;
+        let message = MessageParser::default()
+            .parse(&raw)
+            .ok_or("Failed to parse email")?;
+
+        let mut metadata = HashMap::new();
+        let addresses = [("from", message.from()), ("to", message.to()), ("cc", message.cc())];
+        for (key, address) in addresses {
+            if let Some(address) = address.map(format_addresses).filter(|a| !a.is_empty()) {
+                metadata.insert(key.to_string(), address);
+            }
+        }
+        if let Some(date) = message.date() {
+            metadata.insert("date".to_string(), date.to_rfc3339());
+        }
+        if let Some(subject) = message.subject() {
+            metadata.insert("subject".to_string(), subject.to_string());
+        }
+        metadata.insert("attachments".to_string(), message.attachment_count().to_string());
+
+        // text/plain parts as they are, HTML-only bodies converted to text
+        let text = (0..message.text_body_count())
+            .filter_map(|i| message.body_text(i))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut results = vec![ProcessResult {
+            file_type: FileType::Eml,
+            page_count: 1,
+            pages: vec![PageResult::new(1, text.clone(), ExtractionMethod::DirectText)],
+            text,
+            metadata,
+            ..Default::default()
+        }];
+
+        if message.attachment_count() > 0 {
+            let limits = self.archive_limits;
+            let scratch = tempfile::Builder::new().prefix("ocr_email_").tempdir()?;
+            for (i, attachment) in message.attachments().enumerate() {
+                let name = attachment_file_name(attachment, i + 1);
+                let attachment_path = scratch.path().join(&name);
+                let file_type = detection::sniff(attachment.contents(), &attachment_path);
+                if matches!(file_type, FileType::Unsupported) {
+                    continue;
+                }
+
+                // Too deep to open: the body is kept, each attachment reports why it is missing.
+                // Decoded attachments count against the same budget as archive entries.
+                let skipped = if depth >= limits.max_depth {
+                    Err(ArchiveError::NestingTooDeep(limits.max_depth))
+                } else {
+                    budget.consume(attachment.contents().len() as u64)
+                };
+                if let Err(e) = skipped {
+                    let mut result = ProcessResult::failed(file_type.clone(), &name, e.to_string());
+                    result.metadata = type_mismatch(&attachment_path, &file_type);
+                    results.push(result);
+                    continue;
+                }
+
+                fs::write(&attachment_path, attachment.contents())?;
+                results.extend(self.process_entry(&attachment_path, &name, ocr_engine, depth, budget));
+                let _ = fs::remove_file(&attachment_path);
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// Format a spreadsheet cell: ISO dates, integers without `.0`, no float noise.
+/// Numbers with a percent or fixed-decimal `number_format` are shown as formatted.
+fn format_cell(cell: &Data, number_format: Option<&str>) -> String {
+    match cell {
+        Data::String(s) => s.to_string(),
+        Data::Float(f) => match number_format {
+            Some(code) => format_number_with(*f, code),
+            None => format_number(*f),
+        },
+        Data::Int(i) => match number_format {
+            Some(code) => format_number_with(*i as f64, code),
+            None => i.to_string(),
+        },
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) if dt.is_duration() => {
+            // -0:30:00 has no negative hours to carry the sign
+            let total_seconds = (dt.as_f64() * 86_400.0).round() as i64;
+            let sign = if total_seconds < 0 { "-" } else { "" };
+            let total_seconds = total_seconds.abs();
+            format!(
+                "{}{}:{:02}:{:02}",
+                sign,
+                total_seconds / 3600,
+                total_seconds % 3600 / 60,
+                total_seconds % 60
+            )
+        }
+        Data::DateTime(dt) => {
+            let (year, month, day, hour, minute, second, _) = dt.to_ymd_hms_milli();
+            if dt.as_f64() < 1.0 {
+                format!("{:02}:{:02}:{:02}", hour, minute, second)
+            } else if hour == 0 && minute == 0 && second == 0 {
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            } else {
+                format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, hour, minute, second)
+            }
+        }
+        Data::DateTimeIso(s) => s.clone(),
+        Data::DurationIso(s) => s.clone(),
+        Data::Error(e) => format!("[Error: {:?}]", e),
+        Data::Empty => String::new(),
+    }
+}
+
+/// 3.0 → "3", 0.1 + 0.2 → "0.3" (Excel shows at most 15 significant digits)
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    if value != 0.0 && value.abs() < 1e-6 {
+        return format!("{:e}", value);
+    }
+
+    let decimals = (14 - value.abs().log10().floor() as i32).clamp(0, 15) as usize;
+    let formatted = format!("{:.*}", decimals, value);
+    if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        formatted
+    }
+}
+
+/// Number shown with an Excel format code: `0%` → "25%", `#,##0.00` → "1234.50".
+/// Only the positive section is used and thousands separators are left out;
+/// codes without a percent or decimal layout (scientific, fractions, text) fall back to `format_number`.
+fn format_number_with(value: f64, code: &str) -> String {
+    // Placeholders of the first section, without quoted text, escapes and [colors]
+    let mut pattern = String::new();
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => break,
+            '"' => {
+                chars.by_ref().take_while(|&c| c != '"').for_each(drop);
+            }
+            '[' => {
+                chars.by_ref().take_while(|&c| c != ']').for_each(drop);
+            }
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            '0' | '#' | '?' | '.' | '/' | '%' | 'E' | 'e' => pattern.push(c),
+            _ => {}
+        }
+    }
+
+    if !pattern.contains(['0', '#', '?']) || pattern.contains(['E', 'e', '/']) {
+        return format_number(value);
+    }
+
+    let value = value * 100f64.powi(pattern.matches('%').count() as i32);
+    let fraction = pattern.split_once('.').map(|(_, fraction)| fraction).unwrap_or("");
+    let fixed = fraction.matches(['0', '?']).count();
+    let optional = fraction.matches('#').count();
+
+    // Excel rounds halves away from zero, `format!` to even
+    let decimals = fixed + optional;
+    let scale = 10f64.powi(decimals as i32);
+    let mut formatted = format!("{:.*}", decimals, (value * scale).round() / scale);
+    if optional > 0 {
+        // `0.0#`: the second decimal only when it is not zero
+        let keep = formatted.len() - optional;
+        while formatted.len() > keep && formatted.ends_with('0') {
+            formatted.pop();
+        }
+        if formatted.ends_with('.') {
+            formatted.pop();
+        }
+    }
+    if formatted.starts_with('-') && formatted.trim_start_matches(['-', '0', '.']).is_empty() {
+        // -0.001 shown as "0.00", not "-0.00"
+        formatted.remove(0);
+    }
+
+    formatted.push_str(&"%".repeat(pattern.matches('%').count()));
+    formatted
+}
+
+/// Copy the value of each merged region's top-left cell into the rest of the region.
+/// Regions are clipped to the used range ending at `last`: a title merged across
+/// a whole row (`A1:XFD1`) must not widen every row to 16,384 columns.
+fn fill_merged_regions(grid: &mut Vec<Vec<String>>, origin: (u32, u32), last: (u32, u32), regions: &[Dimensions]) {
+    for region in regions {
+        let Some(value) = grid_cell(grid, origin, region.start).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let value = value.to_string();
+        let end = (region.end.0.min(last.0), region.end.1.min(last.1));
+
+        for row in region.start.0..=end.0 {
+            for col in region.start.1..=end.1 {
+                if (row, col) != region.start {
+                    set_grid_cell(grid, origin, (row, col), value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Cell at an absolute (row, col) position of a grid that starts at `origin`
+fn grid_cell(grid: &[Vec<String>], origin: (u32, u32), position: (u32, u32)) -> Option<&str> {
+    let row = position.0.checked_sub(origin.0)? as usize;
+    let col = position.1.checked_sub(origin.1)? as usize;
+    grid.get(row)?.get(col).map(String::as_str)
+}
+
+/// Set a cell at an absolute position, growing the grid when needed
+fn set_grid_cell(grid: &mut Vec<Vec<String>>, origin: (u32, u32), position: (u32, u32), value: String) {
+    let (Some(row), Some(col)) = (position.0.checked_sub(origin.0), position.1.checked_sub(origin.1)) else {
+        return;
+    };
+    let (row, col) = (row as usize, col as usize);
+
+    let width = grid.first().map(Vec::len).unwrap_or(0).max(col + 1);
+    if grid.len() <= row {
+        grid.resize(row + 1, Vec::new());
+    }
+    for cells in grid.iter_mut() {
+        if cells.len() < width {
+            cells.resize(width, String::new());
+        }
+    }
+    grid[row][col] = value;
+}
+
+/// "Name <address>" list of an email address header
+fn format_addresses(address: &Address) -> String {
+    address
+        .iter()
+        .map(|addr| match (addr.name(), addr.address()) {
+            (Some(name), Some(email)) => format!("{} <{}>", name, email),
+            (None, Some(email)) => email.to_string(),
+            (Some(name), None) => name.to_string(),
+            (None, None) => String::new(),
+        })
+        .filter(|addr| !addr.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// File name of an email attachment, reduced to its last path component.
+/// Unnamed parts are named by position and MIME subtype (`attachment2.pdf`),
+/// forwarded messages get `.eml`.
+fn attachment_file_name(attachment: &MessagePart, position: usize) -> String {
+    let name = attachment
+        .attachment_name()
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && name != "." && name != "..");
+
+    match name {
+        Some(name) if attachment.is_message() && !name.to_lowercase().ends_with(".eml") => {
+            format!("{}.eml", name)
+        }
+        Some(name) => name,
+        None if attachment.is_message() => format!("attachment{}.eml", position),
+        None => {
+            let subtype = attachment
+                .content_type()
+                .and_then(|content_type| content_type.subtype())
+                .unwrap_or("bin");
+            format!("attachment{}.{}", position, subtype)
+        }
+    }
+}
+
+/// `detected_type` / `claimed_type` metadata when the content of `path` is not
+/// what its name says (a PDF saved as .dat, a JPEG named .png); empty otherwise
+fn type_mismatch(path: &Path, detected: &FileType) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    if *detected != FileType::from_path(path) {
+        metadata.insert("detected_type".to_string(), detected.to_string());
+        metadata.insert("claimed_type".to_string(), detection::claimed_type_label(path));
     }
+    metadata
+}
+
+/// Approximate page count of a text document without stored pagination (500 words per page)
+fn estimate_page_count(text: &str) -> usize {
+    let word_count = text.split_whitespace().count();
+    ((word_count as f32 / 500.0).ceil() as usize).max(1)
+}
+
+/// Package entries holding a DOCX part (`word/header1.xml`, `word/footnotes.xml`, ...)
+fn docx_part_names<R: io::Read + io::Seek>(package: &zip::ZipArchive<R>, part: DocxPart) -> Vec<String> {
+    let prefix = match part {
+        DocxPart::Headers => "word/header",
+        DocxPart::Footers => "word/footer",
+        DocxPart::Footnotes => "word/footnotes",
+        DocxPart::Endnotes => "word/endnotes",
+        DocxPart::Comments => "word/comments",
+        DocxPart::TextBoxes => return Vec::new(),
+    };
+
+    // comments.xml only; commentsExtended.xml and friends hold no text
+    office_xml::package_part_names(package, |name| {
+        name.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(".xml"))
+            .is_some_and(|number| number.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Embedded images of a DOCX body in document order, then any other `word/media` images
+fn docx_image_names<R: io::Read + io::Seek>(
+    package: &mut zip::ZipArchive<R>,
+    document: &str,
+    budget: &ExtractionBudget,
+) -> Vec<String> {
+    let relationships = office_xml::relationships(package, "word/document.xml", budget);
+
+    let mut names: Vec<String> = office_xml::image_references(document)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|id| relationships.get(id))
+        .map(|relationship| relationship.target.clone())
+        .collect();
+
+    for name in office_xml::package_part_names(package, |name| name.starts_with("word/media/")) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// OCR raster images stored in an office package: one single-page result per image,
+/// named by its package path (`word/media/image1.png`) rather than numbered as a
+/// page of the document. Vector formats (EMF/WMF) are skipped; so are images
+/// without recognizable text.
+fn ocr_package_images<R: io::Read + io::Seek>(
+    package: &mut zip::ZipArchive<R>,
+    media: &[String],
+    ocr_engine: &OcrEngine,
+    budget: &ExtractionBudget,
+) -> Result<Vec<ProcessResult>, Box<dyn Error>> {
+    let scratch = tempfile::Builder::new().prefix("ocr_media_").tempdir()?;
+    let mut images = Vec::new();
+
+    for name in media {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        let image_path = scratch.path().join(file_name);
+        let Some(data) = office_xml::read_package_bytes(package, name, budget) else {
+            continue;
+        };
+        let file_type = detection::sniff(&data, &image_path);
+        if !matches!(file_type, FileType::Image(_)) {
+            continue;
+        }
+        fs::write(&image_path, data)?;
+
+        match ocr_engine.extract_with_confidence(&image_path) {
+            Ok(analysis) if !analysis.text.trim().is_empty() => {
+                let page = PageResult::from_ocr(1, analysis).with_label(file_name);
+                images.push(ProcessResult {
+                    metadata: type_mismatch(&image_path, &file_type),
+                    file_type,
+                    page_count: 1,
+                    text: page.text.clone(),
+                    pages: vec![page],
+                    entry_name: Some(name.clone()),
+                    ..Default::default()
+                });
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("OCR failed for embedded image {}: {}", name, e),
+        }
+        fs::remove_file(&image_path)?;
+    }
+
+    Ok(images)
+}
+
+/// Pointer from the document text to an embedded image's own OCR result; the
+/// OCR'd text is not repeated, so it is counted once in totals and exports
+fn embedded_image_reference(image: &ProcessResult) -> String {
+    format!("[OCR: {}]\n", image.entry_name.as_deref().unwrap_or_default())
+}
+
+/// Check if pdftoppm (poppler-utils) is available for PDF rasterization
+pub fn check_pdftoppm_installed() -> bool {
+    which::which("pdftoppm").is_ok()
+}
+
+/// Minimum number of letters/digits for a page's text layer to be trusted
+const MIN_PAGE_TEXT_CHARS: usize = 50;
+
+/// Minimum share of readable characters; below it the text layer is likely
+/// garbage from a font without a usable ToUnicode map
+const MIN_READABLE_RATIO: f32 = 0.6;
+
+/// Text-density heuristic: should this page be OCR'd instead of using its text layer?
+fn page_needs_ocr(direct_text: &str) -> bool {
+    let visible: Vec<char> = direct_text.chars().filter(|c| !c.is_whitespace()).collect();
+    let alphanumeric = visible.iter().filter(|c| c.is_alphanumeric()).count();
+
+    if alphanumeric < MIN_PAGE_TEXT_CHARS {
+        return true;
+    }
+
+    let readable = visible
+        .iter()
+        .filter(|c| c.is_alphanumeric() || c.is_ascii_punctuation() || matches!(c, '«' | '»' | '–' | '—' | '№'))
+        .count();
+
+    (readable as f32 / visible.len() as f32) < MIN_READABLE_RATIO
+}
+
+/// Temp directory for rendered PDF pages (removed on drop); fails when pdftoppm is missing
+fn pdf_raster_dir() -> Result<tempfile::TempDir, Box<dyn Error>> {
+    if !check_pdftoppm_installed() {
+        return Err("pdftoppm is not installed (required for --pdf-ocr)!\n\n\
+            Installation instructions:\n\
+            • Windows: https://github.com/oschwartz10612/poppler-windows/releases\n\
+            • Linux: sudo apt install poppler-utils\n\
+            • macOS: brew install poppler".into());
+    }
+
+    Ok(tempfile::Builder::new().prefix("ocr_pdf_").tempdir()?)
+}
+
+/// Render one (1-based) PDF page to PNG at the given DPI
+fn rasterize_pdf_page(
+    path: &Path,
+    page_number: usize,
+    dpi: u32,
+    dir: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    // -singlefile writes <prefix>.png without a page suffix
+    let prefix = dir.join(format!("page-{}", page_number));
+
+    let output = Command::new("pdftoppm")
+        .arg("-r").arg(dpi.to_string())
+        .arg("-f").arg(page_number.to_string())
+        .arg("-l").arg(page_number.to_string())
+        .arg("-singlefile")
+        .arg("-png")
+        .arg(path)
+        .arg(&prefix)
+        .stderr(Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("pdftoppm failed on page {}: {}", page_number, stderr.trim()).into());
+    }
+
+    Ok(prefix.with_extension("png"))
+}
+
+/// `name.tar.gz` / `name.tar.xz`: the stem itself ends with `.tar`
+fn is_tar_stem(path: &Path) -> bool {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase().ends_with(".tar"))
+        .unwrap_or(false)
 }
 
 impl FileType {
+    /// Type from the file's content (magic bytes), the extension only breaks ties
+    pub fn detect(path: &Path) -> Self {
+        detection::detect(path)
+    }
+
+    /// Type claimed by the file extension
     pub fn from_path(path: &Path) -> Self {
         match path.extension().and_then(|ext| ext.to_str()) {
             Some(ext) => {
@@ -258,10 +1422,20 @@ impl FileType {
                     "webp" => FileType::Image(ImageFormat::Webp),
                     "pdf" => FileType::Pdf,
                     "docx" => FileType::Docx,
+                    "pptx" => FileType::Pptx,
+                    "odt" => FileType::Odt,
+                    "rtf" => FileType::Rtf,
+                    "eml" => FileType::Eml,
                     "xlsx" => FileType::Xlsx,
                     "xls" => FileType::Xls,
+                    "xlsb" => FileType::Xlsb,
+                    "ods" => FileType::Ods,
                     "zip" => FileType::Archive(ArchiveFormat::Zip),
                     "tar" => FileType::Archive(ArchiveFormat::Tar),
+                    "tgz" => FileType::Archive(ArchiveFormat::TarGz),
+                    "txz" => FileType::Archive(ArchiveFormat::TarXz),
+                    "gz" if is_tar_stem(path) => FileType::Archive(ArchiveFormat::TarGz),
+                    "xz" if is_tar_stem(path) => FileType::Archive(ArchiveFormat::TarXz),
                     "rar" => FileType::Archive(ArchiveFormat::Rar),
                     _ => FileType::Unsupported,
                 }
@@ -275,10 +1449,64 @@ impl FileType {
             FileType::Image(format) => format!("Image ({:?})", format),
             FileType::Pdf => "PDF".to_string(),
             FileType::Docx => "DOCX".to_string(),
+            FileType::Pptx => "PPTX".to_string(),
+            FileType::Odt => "ODT".to_string(),
+            FileType::Rtf => "RTF".to_string(),
+            FileType::Eml => "EML".to_string(),
             FileType::Xlsx => "XLSX".to_string(),
             FileType::Xls => "XLS".to_string(),
+            FileType::Xlsb => "XLSB".to_string(),
+            FileType::Ods => "ODS".to_string(),
             FileType::Archive(format) => format!("Archive ({:?})", format),
             FileType::Unsupported => "Unsupported".to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_follow_percent_and_decimal_formats() {
+        assert_eq!(format_cell(&Data::Float(0.256), Some("0%")), "26%");
+        assert_eq!(format_cell(&Data::Float(0.25), Some("0.00%")), "25.00%");
+        assert_eq!(format_cell(&Data::Float(1234.5), Some("#,##0.00")), "1234.50");
+        assert_eq!(format_cell(&Data::Float(2.5), Some("0")), "3");
+        assert_eq!(format_cell(&Data::Float(1.5), Some("0.0#")), "1.5");
+        assert_eq!(format_cell(&Data::Float(1.257), Some("0.0#")), "1.26");
+        assert_eq!(format_cell(&Data::Float(-0.001), Some("0.00")), "0.00");
+        assert_eq!(format_cell(&Data::Float(3.0), Some("\"$\"#,##0.00_);[Red](\"$\"#,##0.00)")), "3.00");
+        assert_eq!(format_cell(&Data::Int(7), Some("0.0")), "7.0");
+        // No percent or decimal layout: as without a format
+        assert_eq!(format_cell(&Data::Float(12345.0), Some("0.00E+00")), "12345");
+        assert_eq!(format_cell(&Data::Float(1.5), Some("# ?/?")), "1.5");
+        assert_eq!(format_cell(&Data::Float(0.75), Some("# ??/??")), "0.75");
+        assert_eq!(format_cell(&Data::Float(0.1 + 0.2), Some("@")), "0.3");
+        assert_eq!(format_cell(&Data::Float(0.1 + 0.2), None), "0.3");
+    }
+
+    #[test]
+    fn merged_regions_stay_inside_the_used_range() {
+        let mut grid = vec![
+            vec!["Title".to_string(), String::new()],
+            vec!["a".to_string(), "b".to_string()],
+        ];
+        // Title merged to the last column (B2:XFD2); the used range is B2:C3
+        let regions = [Dimensions { start: (1, 1), end: (1, 16_383) }];
+
+        fill_merged_regions(&mut grid, (1, 1), (2, 2), &regions);
+
+        assert_eq!(grid, vec![vec!["Title", "Title"], vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn negative_durations_keep_their_sign() {
+        use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+        let duration = |days: f64| Data::DateTime(ExcelDateTime::new(days, ExcelDateTimeType::TimeDelta, false));
+        assert_eq!(format_cell(&duration(-0.5 / 24.0), None), "-0:30:00");
+        assert_eq!(format_cell(&duration(-1.5 / 24.0), None), "-1:30:00");
+        assert_eq!(format_cell(&duration(26.25 / 24.0), None), "26:15:00");
+    }
+}
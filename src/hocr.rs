@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use crate::file_processors::PageResult;
+use crate::ocr_engine::{BoundingBox, OcrLine};
+use crate::utils::escape_xml;
+
+fn bbox_title(bbox: &BoundingBox) -> String {
+    format!("bbox {} {} {} {}", bbox.left, bbox.top, bbox.right(), bbox.bottom())
+}
+
+/// Render the OCR'd pages of a document as Tesseract-compatible hOCR.
+/// Returns `None` when no page has OCR layout (e.g. digital PDFs, DOCX).
+pub fn render_hocr(filename: &str, pages: &[PageResult]) -> Option<String> {
+    let ocr_pages: Vec<(&PageResult, _)> = pages
+        .iter()
+        .filter_map(|p| p.ocr.as_ref().map(|ocr| (p, ocr)))
+        .collect();
+
+    if ocr_pages.is_empty() {
+        return None;
+    }
+
+    // Document language: the one detected on most OCR'd pages (ISO 639-3)
+    let mut page_languages: BTreeMap<&str, usize> = BTreeMap::new();
+    for (page, _) in &ocr_pages {
+        if let Some(lang) = page.language.as_deref() {
+            *page_languages.entry(lang).or_default() += 1;
+        }
+    }
+    let document_lang = page_languages
+        .iter()
+        .max_by_key(|(_, count)| **count)
+        .map(|(lang, _)| format!(" xml:lang=\"{0}\" lang=\"{0}\"", escape_xml(lang)))
+        .unwrap_or_default();
+
+    let mut hocr = String::new();
+    hocr.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    hocr.push_str("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \
+                   \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n");
+    hocr.push_str(&format!("<html xmlns=\"http://www.w3.org/1999/xhtml\"{}>\n", document_lang));
+    hocr.push_str(" <head>\n");
+    hocr.push_str(&format!("  <title>{}</title>\n", escape_xml(filename)));
+    hocr.push_str("  <meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>\n");
+    hocr.push_str(&format!("  <meta name=\"ocr-system\" content=\"advanced-ocr {} (tesseract)\"/>\n",
+                           env!("CARGO_PKG_VERSION")));
+    hocr.push_str("  <meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word ocrp_wconf\"/>\n");
+    hocr.push_str(" </head>\n <body>\n");
+
+    for (page, ocr) in ocr_pages {
+        let n = page.page_number;
+        let page_bbox = ocr
+            .pages
+            .first()
+            .map(|p| BoundingBox { left: 0, top: 0, width: p.width, height: p.height })
+            .unwrap_or_default();
+
+        hocr.push_str(&format!(
+            "  <div class=\"ocr_page\" id=\"page_{}\" title=\"image &quot;{}&quot;; {}; ppageno {}\">\n",
+            n,
+            escape_xml(filename),
+            bbox_title(&page_bbox),
+            n - 1
+        ));
+
+        // Group lines into blocks and paragraphs (TSV order is reading order)
+        let mut blocks: BTreeMap<u32, BTreeMap<u32, Vec<OcrLine>>> = BTreeMap::new();
+        for line in ocr.lines() {
+            blocks
+                .entry(line.block_num)
+                .or_default()
+                .entry(line.par_num)
+                .or_default()
+                .push(line);
+        }
+
+        let lang = page
+            .language
+            .as_deref()
+            .map(|l| format!(" lang=\"{}\"", escape_xml(l)))
+            .unwrap_or_default();
+        let mut line_id = 0;
+
+        for (block_num, paragraphs) in &blocks {
+            let block_bbox = paragraphs
+                .values()
+                .flatten()
+                .map(|l| l.bbox)
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or_default();
+            hocr.push_str(&format!(
+                "   <div class=\"ocr_carea\" id=\"block_{}_{}\" title=\"{}\">\n",
+                n, block_num, bbox_title(&block_bbox)
+            ));
+
+            for (par_num, lines) in paragraphs {
+                let par_bbox = lines
+                    .iter()
+                    .map(|l| l.bbox)
+                    .reduce(|a, b| a.union(&b))
+                    .unwrap_or_default();
+                hocr.push_str(&format!(
+                    "    <p class=\"ocr_par\" id=\"par_{}_{}_{}\"{} title=\"{}\">\n",
+                    n, block_num, par_num, lang, bbox_title(&par_bbox)
+                ));
+
+                for line in lines {
+                    line_id += 1;
+                    hocr.push_str(&format!(
+                        "     <span class=\"ocr_line\" id=\"line_{}_{}\" title=\"{}\">\n",
+                        n, line_id, bbox_title(&line.bbox)
+                    ));
+
+                    for word in &line.words {
+                        // Tesseract numbers words within their line
+                        hocr.push_str(&format!(
+                            "      <span class=\"ocrx_word\" id=\"word_{}_{}_{}_{}_{}\" title=\"{}; x_wconf {:.0}\">{}</span>\n",
+                            n,
+                            block_num,
+                            par_num,
+                            word.line_num,
+                            word.word_num,
+                            bbox_title(&word.bbox),
+                            word.confidence.max(0.0),
+                            escape_xml(&word.text)
+                        ));
+                    }
+
+                    hocr.push_str("     </span>\n");
+                }
+
+                hocr.push_str("    </p>\n");
+            }
+
+            hocr.push_str("   </div>\n");
+        }
+
+        hocr.push_str("  </div>\n");
+    }
+
+    hocr.push_str(" </body>\n</html>\n");
+    Some(hocr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processors::ExtractionMethod;
+    use crate::ocr_engine::{OcrAnalysisResult, OcrPage, OcrWordResult};
+
+    fn word(text: &str, word_num: u32, left: u32, confidence: f32) -> OcrWordResult {
+        OcrWordResult {
+            text: text.to_string(),
+            confidence,
+            page_num: 1,
+            block_num: 1,
+            par_num: 1,
+            line_num: 1,
+            word_num,
+            bbox: BoundingBox { left, top: 20, width: 40, height: 12 },
+        }
+    }
+
+    #[test]
+    fn words_carry_boxes_and_confidences() {
+        let analysis = OcrAnalysisResult {
+            text: "R&D <draft>".to_string(),
+            pages: vec![OcrPage { page_num: 1, width: 300, height: 200 }],
+            words: vec![word("R&D", 1, 10, 96.4), word("<draft>", 2, 60, 71.6)],
+            avg_confidence: 84.0,
+            detected_language: Some("eng".to_string()),
+            language_confidence: Some(0.9),
+            rotation: 0,
+            skew_angle: 0.0,
+        };
+        let page = PageResult::from_ocr(1, analysis);
+
+        let hocr = render_hocr("scan.png", &[page]).unwrap();
+
+        assert!(hocr.contains("id=\"page_1\" title=\"image &quot;scan.png&quot;; bbox 0 0 300 200; ppageno 0\""));
+        assert!(hocr.contains("<span class=\"ocr_line\" id=\"line_1_1\" title=\"bbox 10 20 100 32\">"));
+        assert!(hocr.contains(
+            "<span class=\"ocrx_word\" id=\"word_1_1_1_1_1\" title=\"bbox 10 20 50 32; x_wconf 96\">R&amp;D</span>"
+        ));
+        assert!(hocr.contains(
+            "<span class=\"ocrx_word\" id=\"word_1_1_1_1_2\" title=\"bbox 60 20 100 32; x_wconf 72\">&lt;draft&gt;</span>"
+        ));
+        assert!(hocr.contains("lang=\"eng\""));
+    }
+
+    #[test]
+    fn pages_without_ocr_layout_have_no_hocr() {
+        let page = PageResult::new(1, "digital text".to_string(), ExtractionMethod::DirectText);
+        assert!(render_hocr("doc.pdf", &[page]).is_none());
+    }
+}
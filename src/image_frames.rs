@@ -0,0 +1,204 @@
+use std::error::Error;
+use std::fs;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder};
+
+use crate::file_processors::ImageFormat;
+
+/// Upper bound on IFDs followed in a TIFF chain (guards against offset loops)
+const MAX_TIFF_FRAMES: usize = 10_000;
+
+/// Upper bound on distinct GIF frames OCR'd; longer animations are cut off
+const MAX_GIF_FRAMES: usize = 100;
+
+/// Pages of an image file ready for OCR: the file itself when it holds a single
+/// frame, otherwise one PNG per frame in a temp directory removed on drop
+pub struct ImageFrames {
+    _dir: Option<tempfile::TempDir>,
+    frames: Vec<PathBuf>,
+    undecoded: bool,
+}
+
+impl ImageFrames {
+    pub fn frames(&self) -> &[PathBuf] {
+        &self.frames
+    }
+
+    /// Some page could not be decoded here (e.g. CCITT fax compression); the
+    /// original file is the only frame and Tesseract has to split the pages itself
+    pub fn undecoded(&self) -> bool {
+        self.undecoded
+    }
+}
+
+/// Split multi-page TIFFs (every IFD) and animated GIFs (every distinct frame) into pages
+pub fn split_frames(path: &Path, format: &ImageFormat) -> Result<ImageFrames, Box<dyn Error>> {
+    let single = || ImageFrames {
+        _dir: None,
+        frames: vec![path.to_path_buf()],
+        undecoded: false,
+    };
+
+    match format {
+        ImageFormat::Tiff => {
+            let mut data = fs::read(path)?;
+            let offsets = tiff_ifd_offsets(&data).unwrap_or_default();
+            if offsets.len() <= 1 {
+                return Ok(single());
+            }
+
+            let dir = tempfile::Builder::new().prefix("ocr_frames_").tempdir()?;
+            let mut frames = Vec::new();
+            for (i, offset) in offsets.into_iter().enumerate() {
+                // Point the header at this IFD so the decoder reads it as the first image
+                set_first_ifd(&mut data, offset);
+                let frame_path = dir.path().join(format!("frame_{:04}.png", i + 1));
+                if let Err(e) = save_tiff_frame(&data, &frame_path) {
+                    log::warn!("{}: page {} cannot be decoded ({}), OCR'ing the whole file", path.display(), i + 1, e);
+                    return Ok(ImageFrames {
+                        undecoded: true,
+                        ..single()
+                    });
+                }
+                frames.push(frame_path);
+            }
+
+            Ok(ImageFrames {
+                _dir: Some(dir),
+                frames,
+                undecoded: false,
+            })
+        }
+        ImageFormat::Gif => {
+            let decoder = GifDecoder::new(BufReader::new(fs::File::open(path)?))?;
+            let dir = tempfile::Builder::new().prefix("ocr_frames_").tempdir()?;
+            let mut frames = Vec::new();
+            let mut previous: Option<image::RgbaImage> = None;
+
+            for frame in decoder.into_frames() {
+                // Animations repeat the same picture across frames; OCR it once
+                let buffer = frame?.into_buffer();
+                if previous.as_ref() == Some(&buffer) {
+                    continue;
+                }
+                if frames.len() == MAX_GIF_FRAMES {
+                    log::warn!("{}: more than {} frames, the rest are skipped", path.display(), MAX_GIF_FRAMES);
+                    break;
+                }
+                let frame_path = dir.path().join(format!("frame_{:04}.png", frames.len() + 1));
+                buffer.save(&frame_path)?;
+                frames.push(frame_path);
+                previous = Some(buffer);
+            }
+
+            if frames.len() <= 1 {
+                return Ok(single());
+            }
+            Ok(ImageFrames {
+                _dir: Some(dir),
+                frames,
+                undecoded: false,
+            })
+        }
+        _ => Ok(single()),
+    }
+}
+
+/// Decode the first image of `data`, turn it upright per its Orientation tag
+/// and save it as PNG
+fn save_tiff_frame(data: &[u8], frame_path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut decoder = TiffDecoder::new(Cursor::new(data))?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    image.save(frame_path)?;
+    Ok(())
+}
+
+/// Byte order and layout of a classic or BigTIFF header
+struct TiffLayout {
+    big_endian: bool,
+    bigtiff: bool,
+}
+
+impl TiffLayout {
+    fn read(&self, data: &[u8], offset: usize, size: usize) -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let byte = if self.big_endian { bytes[i] } else { bytes[size - 1 - i] };
+            value = (value << 8) | byte as u64;
+        }
+        Some(value)
+    }
+}
+
+fn tiff_layout(data: &[u8]) -> Option<TiffLayout> {
+    let big_endian = match data.get(0..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let mut layout = TiffLayout {
+        big_endian,
+        bigtiff: false,
+    };
+    match layout.read(data, 2, 2)? {
+        42 => {}
+        43 => layout.bigtiff = true,
+        _ => return None,
+    }
+    Some(layout)
+}
+
+/// Offsets of every page IFD in the chain starting at the header; reduced-resolution
+/// images (thumbnails, `NewSubfileType` bit 0) are left out
+fn tiff_ifd_offsets(data: &[u8]) -> Option<Vec<u64>> {
+    let layout = tiff_layout(data)?;
+    // Entry count, entry and next-offset sizes differ between classic TIFF and BigTIFF
+    let (first, count_size, entry_size, value_at, next_size) = if layout.bigtiff {
+        (layout.read(data, 8, 8)?, 8, 20, 12, 8)
+    } else {
+        (layout.read(data, 4, 4)?, 2, 12, 8, 4)
+    };
+
+    let mut visited = Vec::new();
+    let mut offsets = Vec::new();
+    let mut offset = first;
+    while offset != 0 && visited.len() < MAX_TIFF_FRAMES && !visited.contains(&offset) {
+        visited.push(offset);
+        let entries = layout.read(data, offset as usize, count_size)? as usize;
+        let first_entry = (offset as usize).checked_add(count_size)?;
+
+        let reduced = (0..entries).any(|i| {
+            let entry = first_entry + i * entry_size;
+            let is_subfile_type = layout.read(data, entry, 2) == Some(254);
+            // SHORT (3) or LONG (4), stored inline
+            let size = if layout.read(data, entry + 2, 2) == Some(3) { 2 } else { 4 };
+            is_subfile_type && layout.read(data, entry + value_at, size).is_some_and(|v| v & 1 == 1)
+        });
+        if !reduced {
+            offsets.push(offset);
+        }
+
+        let next_at = first_entry.checked_add(entries.checked_mul(entry_size)?)?;
+        offset = layout.read(data, next_at, next_size).unwrap_or(0);
+    }
+    Some(offsets)
+}
+
+fn set_first_ifd(data: &mut [u8], offset: u64) {
+    let Some(layout) = tiff_layout(data) else {
+        return;
+    };
+    let (at, size) = if layout.bigtiff { (8, 8) } else { (4, 4) };
+    let bytes = offset.to_be_bytes();
+    let bytes = &bytes[8 - size..];
+    for i in 0..size {
+        data[at + i] = if layout.big_endian { bytes[i] } else { bytes[size - 1 - i] };
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+use exif::{DateTime, Exif, In, Reader, Tag, Value};
+
+/// EXIF fields of a photo (JPEG, TIFF, PNG, WebP, HEIF): camera, capture time,
+/// GPS position, resolution and orientation. Missing or broken fields are left out.
+pub fn exif_properties(path: &Path) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    let Ok(file) = fs::File::open(path) else {
+        return properties;
+    };
+    let Ok(exif) = Reader::new().read_from_container(&mut BufReader::new(file)) else {
+        return properties;
+    };
+
+    let make = ascii(&exif, Tag::Make);
+    let model = ascii(&exif, Tag::Model);
+    // Models often repeat the make ("Canon" / "Canon EOS 80D")
+    let camera = match (make, model) {
+        (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    if let Some(camera) = camera {
+        properties.insert("camera".to_string(), camera);
+    }
+    if let Some(software) = ascii(&exif, Tag::Software) {
+        properties.insert("software".to_string(), software);
+    }
+
+    if let Some(captured) = capture_time(&exif) {
+        properties.insert("captured".to_string(), captured);
+    }
+
+    if let (Some(latitude), Some(longitude)) = (
+        coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, 'S'),
+        coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, 'W'),
+    ) {
+        properties.insert("gps_latitude".to_string(), format!("{:.6}", latitude));
+        properties.insert("gps_longitude".to_string(), format!("{:.6}", longitude));
+        if let Some(altitude) = rational(&exif, Tag::GPSAltitude, In::PRIMARY) {
+            // GPSAltitudeRef 1 means below sea level
+            let below = exif
+                .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+                == Some(1);
+            let altitude = if below { -altitude } else { altitude };
+            properties.insert("gps_altitude".to_string(), format!("{:.1} m", altitude));
+        }
+    }
+
+    if let Some(dpi) = resolution(&exif) {
+        properties.insert("dpi".to_string(), dpi);
+    }
+
+    if let Some(orientation) = exif
+        .get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .filter(|&v| v != 1)
+    {
+        properties.insert("exif_orientation".to_string(), orientation.to_string());
+    }
+
+    properties
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => {
+            let text = String::from_utf8_lossy(values.first()?);
+            let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            (!text.is_empty()).then(|| text.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn rational(exif: &Exif, tag: Tag, ifd: In) -> Option<f64> {
+    match &exif.get_field(tag, ifd)?.value {
+        Value::Rational(values) => values.first().filter(|v| v.denom != 0).map(|v| v.to_f64()),
+        _ => None,
+    }
+}
+
+/// DateTimeOriginal (or DateTime) as "YYYY-MM-DD HH:MM:SS", with the
+/// OffsetTimeOriginal time zone appended when the camera recorded it
+fn capture_time(exif: &Exif) -> Option<String> {
+    let (field, offset_tag) = match exif.get_field(Tag::DateTimeOriginal, In::PRIMARY) {
+        Some(field) => (field, Tag::OffsetTimeOriginal),
+        None => (exif.get_field(Tag::DateTime, In::PRIMARY)?, Tag::OffsetTime),
+    };
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let mut captured = DateTime::from_ascii(values.first()?).ok()?;
+
+    if let Some(Value::Ascii(offset)) = exif.get_field(offset_tag, In::PRIMARY).map(|f| &f.value)
+        && let Some(offset) = offset.first()
+    {
+        let _ = captured.parse_offset(offset);
+    }
+
+    Some(match captured.offset {
+        Some(minutes) => format!(
+            "{} {}{:02}:{:02}",
+            captured,
+            if minutes < 0 { '-' } else { '+' },
+            minutes.abs() / 60,
+            minutes.abs() % 60
+        ),
+        None => captured.to_string(),
+    })
+}
+
+/// Degrees, minutes, seconds to signed decimal degrees (negative south / west)
+fn coordinate(exif: &Exif, tag: Tag, reference_tag: Tag, negative: char) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    if parts.len() < 3 || parts.iter().any(|part| part.denom == 0) {
+        return None;
+    }
+    let degrees = parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0;
+
+    let reference = ascii(exif, reference_tag).unwrap_or_default();
+    Some(if reference.starts_with(negative) { -degrees } else { degrees })
+}
+
+/// XResolution x YResolution in dots per inch (ResolutionUnit 3 is centimeters)
+fn resolution(exif: &Exif) -> Option<String> {
+    let x = rational(exif, Tag::XResolution, In::PRIMARY)?;
+    let y = rational(exif, Tag::YResolution, In::PRIMARY).unwrap_or(x);
+    let unit = exif
+        .get_field(Tag::ResolutionUnit, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(2);
+
+    let (x, y) = match unit {
+        2 => (x, y),
+        3 => (x * 2.54, y * 2.54),
+        // 1: no absolute unit, only the aspect ratio
+        _ => return None,
+    };
+    Some(format!("{}x{}", x.round(), y.round()))
+}
@@ -9,22 +9,36 @@ use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
+mod alto;
+mod archive;
+mod detection;
 mod file_processors;
+mod hocr;
+mod image_frames;
+mod image_metadata;
 mod ocr_engine;
+mod office_xml;
 mod utils;
 mod pdf_creator;
+mod preprocess;
+mod rtf;
 
-use crate::file_processors::{FileProcessor, FileType};
+use crate::archive::ArchiveLimits;
+use crate::file_processors::{DocxPart, FileProcessor, FileType, PageResult};
 use crate::ocr_engine::OcrEngine;
-use crate::pdf_creator::{create_searchable_pdf, PdfCreationMethod};
-use crate::utils::{extract_metadata, generate_report, save_results};
+use crate::pdf_creator::{create_searchable_pdf, PdfCreationMethod, PdfPage};
+use crate::preprocess::{Binarization, Preprocessing, PreprocessStep};
+use crate::utils::{correction_metadata, generate_report, save_results, OutputOptions};
 
 #[derive(Debug, Clone, serde::Serialize)]
 struct OcrResult {
     filename: String,
+    #[serde(skip)]
+    path: PathBuf,
     file_type: String,
     page_count: usize,
     text: String,
+    pages: Vec<PageResult>,
     processing_time_ms: u128,
     error: Option<String>,
     metadata: HashMap<String, String>,
@@ -34,7 +48,7 @@ struct OcrResult {
 enum PdfMethod {
     /// Use ocrmypdf (Python) - best quality, requires installation
     Ocrmypdf,
-    /// Use native Rust (lopdf) - fast, no dependencies
+    /// Use native Rust (pdf-writer) - fast, invisible text layer from OCR word boxes
     Native,
 }
 
@@ -47,7 +61,7 @@ fn default_workers() -> usize {
 /// Advanced Batch OCR in Rust
 #[derive(Parser, Debug)]
 #[command(name = "Advanced OCR")]
-#[command(about = "Batch OCR for PDF, DOCX, XLSX, and images", long_about = None)]
+#[command(about = "Batch OCR for PDF, DOCX, PPTX, spreadsheets, and images", long_about = None)]
 struct Cli {
     /// Input directory path
     #[arg(short, long, default_value = "./input")]
@@ -77,6 +91,18 @@ struct Cli {
     #[arg(long, default_value = "true")]
     save_texts: bool,
 
+    /// Save hOCR files (word boxes and confidences) for OCR'd documents
+    #[arg(long)]
+    hocr: bool,
+
+    /// Save ALTO v4 XML for every OCR'd page
+    #[arg(long)]
+    alto: bool,
+
+    /// Export each spreadsheet sheet as CSV (tables/<file.ext>/<sheet>.csv)
+    #[arg(long)]
+    tables: bool,
+
     /// Create searchable PDFs from images
     #[arg(long)]
     searchable_pdf: bool,
@@ -97,10 +123,56 @@ struct Cli {
     #[arg(long, default_value = "3")]
     oem: u8,
 
+    /// Image preprocessing before OCR (comma-separated: grayscale, orient, deskew,
+    /// crop-borders, upscale, normalize, binarize, despeckle; always run in that order)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    preprocess: Vec<PreprocessStep>,
+
+    /// Thresholding method for the binarize preprocessing step
+    #[arg(long, value_enum, default_value = "sauvola")]
+    binarization: Binarization,
+
+    /// Save the image after each preprocessing step to this directory
+    #[arg(long)]
+    preprocess_debug_dir: Option<PathBuf>,
+
     /// Enable detailed OCR quality analysis
     #[arg(long, default_value = "true")]
     analyze_quality: bool,
 
+    /// Output spreadsheet formulas as text (=SUM(A1:A3)) instead of their cached results
+    #[arg(long)]
+    excel_formulas: bool,
+
+    /// DOCX parts to extract besides the body (comma-separated)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "headers,footers,footnotes,endnotes,comments,text-boxes"
+    )]
+    docx_parts: Vec<DocxPart>,
+
+    /// OCR images embedded in DOCX and PPTX files
+    #[arg(long)]
+    ocr_embedded_images: bool,
+
+    /// Maximum total uncompressed size of an archive, in MB
+    #[arg(long, default_value_t = ArchiveLimits::default().max_total_size / (1024 * 1024))]
+    max_archive_size_mb: u64,
+
+    /// Maximum number of entries in an archive
+    #[arg(long, default_value_t = ArchiveLimits::default().max_entries)]
+    max_archive_entries: usize,
+
+    /// Maximum archive compression ratio (zip-bomb guard)
+    #[arg(long, default_value_t = ArchiveLimits::default().max_compression_ratio)]
+    max_compression_ratio: u64,
+
+    /// Maximum nesting depth of archives inside archives
+    #[arg(long, default_value_t = ArchiveLimits::default().max_depth)]
+    max_archive_depth: usize,
+
     /// Show detailed Tesseract commands and debug output
     #[arg(long, short = 'v')]
     verbose: bool,
@@ -163,7 +235,7 @@ fn collect_files(input_dir: &Path) -> Vec<PathBuf> {
     {
         let path = entry.path();
         if path.is_file() {
-            let file_type = FileType::from_path(path);
+            let file_type = FileType::detect(path);
             if !matches!(file_type, FileType::Unsupported) {
                 files.push(path.to_path_buf());
             }
@@ -194,28 +266,44 @@ fn process_single_file(
         Ok(process_results) => {
             for result in process_results {
                 let processing_time = start.elapsed().as_millis();
-                let metadata = extract_metadata(&path, &result.file_type);
+
+                // Archive entries: archive.zip!/inner/path.pdf
+                let (entry_filename, mut metadata) = match &result.entry_name {
+                    Some(entry_name) => {
+                        let mut metadata = HashMap::new();
+                        metadata.insert("path".to_string(), format!("{}!/{}", path.display(), entry_name));
+                        (format!("{}!/{}", filename, entry_name), metadata)
+                    }
+                    // process_file already collected the file's own metadata
+                    None => (filename.clone(), HashMap::new()),
+                };
+                metadata.extend(result.metadata);
+                metadata.extend(correction_metadata(&result.pages));
 
                 results.push(OcrResult {
-                    filename: filename.clone(),
+                    filename: entry_filename,
+                    path: path.clone(),
                     file_type: result.file_type.to_string(),
                     page_count: result.page_count,
                     text: result.text,
+                    pages: result.pages,
                     processing_time_ms: processing_time,
-                    error: None,
+                    error: result.error.map(|e| format!("Processing error: {}", e)),
                     metadata,
                 });
             }
         }
         Err(e) => {
             let processing_time = start.elapsed().as_millis();
-            let file_type = FileType::from_path(&path);
+            let file_type = FileType::detect(&path);
 
             results.push(OcrResult {
                 filename,
+                path: path.clone(),
                 file_type: file_type.to_string(),
                 page_count: 0,
                 text: String::new(),
+                pages: Vec::new(),
                 processing_time_ms: processing_time,
                 error: Some(format!("Processing error: {}", e)),
                 metadata: HashMap::new(),
@@ -254,7 +342,9 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     println!("\nSupported formats:");
     println!("  - Images: jpg, jpeg, png, bmp, tiff, gif, webp");
-    println!("  - Documents: pdf, docx, xlsx, xls");
+    println!("  - Documents: pdf, docx, pptx, odt, rtf, xlsx, xls, xlsb, ods");
+    println!("  - Archives: zip, tar, tar.gz/tgz, tar.xz/txz");
+    println!("  - Email: eml (attachments are processed as well)");
 
     // Collect files
     let files = collect_files(&cli.input);
@@ -284,6 +374,14 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("OCR DPI: {}", dpi);
     println!("PDF OCR: {}", if cli.pdf_ocr { "enabled" } else { "disabled" });
 
+    if cli.pdf_ocr && !file_processors::check_pdftoppm_installed() {
+        eprintln!("\n⚠️  pdftoppm is not installed, scanned PDF pages cannot be OCR'd");
+        eprintln!("\n📦 To enable PDF OCR install poppler:");
+        eprintln!("  • Windows: https://github.com/oschwartz10612/poppler-windows/releases");
+        eprintln!("  • Linux: sudo apt install poppler-utils");
+        eprintln!("  • macOS: brew install poppler\n");
+    }
+
     // Validate languages (optional, can be skipped for speed)
     if let Err(e) = OcrEngine::validate_languages(&cli.languages) {
         eprintln!("Warning: {}", e);
@@ -305,10 +403,24 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     // Initialize OCR engine
-    let ocr_engine = OcrEngine::with_config(&cli.languages, dpi, cli.psm, cli.oem, cli.verbose)?;
+    let ocr_engine = OcrEngine::with_config(&cli.languages, dpi, cli.psm, cli.oem, cli.verbose)?
+        .with_preprocessing(
+            Preprocessing::new(&cli.preprocess)
+                .with_binarization(cli.binarization)
+                .with_debug_dir(cli.preprocess_debug_dir.clone()),
+        );
 
     // Initialize file processor
-    let processor = FileProcessor::new(cli.pdf_ocr);
+    let processor = FileProcessor::new(cli.pdf_ocr)
+        .with_spreadsheet_formulas(cli.excel_formulas)
+        .with_docx_parts(&cli.docx_parts)
+        .with_embedded_image_ocr(cli.ocr_embedded_images)
+        .with_archive_limits(ArchiveLimits {
+            max_total_size: cli.max_archive_size_mb * 1024 * 1024,
+            max_entries: cli.max_archive_entries,
+            max_compression_ratio: cli.max_compression_ratio,
+            max_depth: cli.max_archive_depth,
+        });
 
     // Determine optimal worker count
     let cpu_count = std::thread::available_parallelism()
@@ -354,7 +466,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     // Save results and generate report
-    save_results(&results, &cli.output, cli.save_texts)?;
+    let output_options = OutputOptions {
+        save_texts: cli.save_texts,
+        hocr: cli.hocr,
+        alto: cli.alto,
+        tables: cli.tables,
+    };
+    save_results(&results, &cli.output, &output_options)?;
     generate_report(&results, &cli.output)?;
 
     // Display final statistics
@@ -375,47 +493,46 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("\n📊 OCR Quality Analysis");
         println!("{}", "─".repeat(80));
 
-        for file in &files {
-            if matches!(FileType::from_path(file), FileType::Image(_)) {
-                match ocr_engine.extract_with_confidence(file) {
-                    Ok(analysis) => {
-                        if !analysis.words.is_empty() {
-                            // Low confidence words
-                            let low_conf_words: Vec<&str> = analysis.words.iter()
-                                .filter(|w| w.confidence < 70.0)
-                                .take(3)
-                                .map(|w| w.text.as_str())
-                                .collect();
-
-                            // Print filename and OCR quality
-                            print!("{:<35} OCR: {:5.1}%",
-                                   file.file_name().unwrap().to_string_lossy(),
-                                   analysis.avg_confidence
-                            );
-
-                            // Print detected language
-                            if let Some(lang) = &analysis.detected_language {
-                                let conf = analysis.language_confidence
-                                    .map(|c| format!("{:.0}%", c * 100.0))
-                                    .unwrap_or_else(|| "?".to_string());
-                                print!("  {:4} ({:3})", lang, conf);
-                            }
-
-                            // Print low confidence words warning
-                            if !low_conf_words.is_empty() {
-                                print!("  ⚠️ [{}]", low_conf_words.join(", "));
-                            }
-
-                            println!();
-                        } else {
-                            println!("{:<35} N/A (no text)",
-                                     file.file_name().unwrap().to_string_lossy()
-                            );
-                        }
+        // Reuse the analysis stored during processing instead of running Tesseract again
+        for result in &results {
+            let ocr_pages: Vec<&PageResult> = result.pages.iter().filter(|p| p.ocr.is_some()).collect();
+
+            for page in &ocr_pages {
+                let Some(analysis) = &page.ocr else { continue };
+
+                let label = if ocr_pages.len() > 1 {
+                    format!("{} (p.{})", result.filename, page.page_number)
+                } else {
+                    result.filename.clone()
+                };
+
+                if !analysis.words.is_empty() {
+                    // Low confidence words
+                    let low_conf_words: Vec<&str> = analysis.words.iter()
+                        .filter(|w| w.confidence < 70.0)
+                        .take(3)
+                        .map(|w| w.text.as_str())
+                        .collect();
+
+                    // Print filename and OCR quality
+                    print!("{:<35} OCR: {:5.1}%", label, analysis.avg_confidence);
+
+                    // Print detected language
+                    if let Some(lang) = &analysis.detected_language {
+                        let conf = analysis.language_confidence
+                            .map(|c| format!("{:.0}%", c * 100.0))
+                            .unwrap_or_else(|| "?".to_string());
+                        print!("  {:4} ({:3})", lang, conf);
                     }
-                    Err(e) => {
-                        eprintln!("  ✗ {}: {}", file.file_name().unwrap().to_string_lossy(), e);
+
+                    // Print low confidence words warning
+                    if !low_conf_words.is_empty() {
+                        print!("  ⚠️ [{}]", low_conf_words.join(", "));
                     }
+
+                    println!();
+                } else {
+                    println!("{:<35} N/A (no text)", label);
                 }
             }
         }
@@ -437,27 +554,38 @@ fn main() -> Result<(), Box<dyn Error>> {
                     eprintln!("  • Linux: sudo apt install ocrmypdf");
                     eprintln!("  • macOS: brew install ocrmypdf");
                     eprintln!("  📚 More info: https://ocrmypdf.readthedocs.io/en/latest/installation.html");
-                    eprintln!("\n⚠️  Native method will create image-only PDFs (text not searchable)\n");
-                    (PdfCreationMethod::Native, "native (image-only)")
+                    eprintln!();
+                    (PdfCreationMethod::Native, "native")
                 }
             }
-            PdfMethod::Native => {
-                eprintln!("\n⚠️  Using native Rust method - PDFs will contain images only (not searchable)");
-                eprintln!("💡 For searchable PDFs, use --pdf-method ocrmypdf\n");
-                (PdfCreationMethod::Native, "native (image-only)")
-            }
+            PdfMethod::Native => (PdfCreationMethod::Native, "native"),
         };
 
         println!("🔍 Creating PDFs using: {}", method_name);
         let pdf_output = cli.output.join("searchable_pdfs");
         std::fs::create_dir_all(&pdf_output)?;
 
-        for (file, result) in files.iter().zip(results.iter()) {
-            if matches!(FileType::from_path(file), FileType::Image(_)) && result.error.is_none() {
+        for result in &results {
+            let file = &result.path;
+            if let FileType::Image(format) = FileType::detect(file) && result.error.is_none() {
                 let output_name = file.file_stem().unwrap().to_string_lossy();
                 let output_pdf = pdf_output.join(format!("{}.pdf", output_name));
 
-                match create_searchable_pdf(file, &result.text, &output_pdf, &cli.languages, method) {
+                // One PDF page per TIFF page / GIF frame, each with its own OCR result
+                let created = image_frames::split_frames(file, &format).and_then(|frames| {
+                    let pages: Vec<PdfPage> = frames
+                        .frames()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, image)| PdfPage {
+                            image,
+                            ocr: result.pages.get(i).and_then(|p| p.ocr.as_ref()),
+                        })
+                        .collect();
+                    create_searchable_pdf(&pages, &output_pdf, &cli.languages, method)
+                });
+
+                match created {
                     Ok(_) => println!("  ✓ {}", output_name),
                     Err(e) => eprintln!("  ✗ {}: {}", output_name, e),
                 }
@@ -1,6 +1,13 @@
 use std::error::Error;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::Command;
+
+use image::metadata::Orientation;
+
+use crate::preprocess::Preprocessing;
+
+/// OSD results below this confidence are ignored (ocrmypdf's default threshold)
+const MIN_ORIENTATION_CONFIDENCE: f32 = 14.0;
 
 pub struct OcrEngine {
     language: String,
@@ -8,20 +15,93 @@ pub struct OcrEngine {
     psm: u8,
     oem: u8,
     verbose: bool,
+    preprocessing: Preprocessing,
+}
+
+/// Pixel rectangle in the OCR'd image (origin at the top-left corner)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BoundingBox {
+    pub fn right(&self) -> u32 {
+        self.left + self.width
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.top + self.height
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        BoundingBox {
+            left,
+            top,
+            width: self.right().max(other.right()) - left,
+            height: self.bottom().max(other.bottom()) - top,
+        }
+    }
 }
 
+/// Recognized word with its position in the Tesseract layout hierarchy
+/// (page → block → paragraph → line → word)
 #[derive(Debug, Clone)]
 pub struct OcrWordResult {
     pub text: String,
     pub confidence: f32,
+    pub page_num: u32,
+    pub block_num: u32,
+    pub par_num: u32,
+    pub line_num: u32,
+    pub word_num: u32,
+    pub bbox: BoundingBox,
+}
+
+/// Page size as reported by Tesseract (TSV level 1)
+#[derive(Debug, Clone, Copy)]
+pub struct OcrPage {
+    pub page_num: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Words of one text line, in reading order
+#[derive(Debug, Clone)]
+pub struct OcrLine<'a> {
+    pub page_num: u32,
+    pub block_num: u32,
+    pub par_num: u32,
+    pub line_num: u32,
+    pub bbox: BoundingBox,
+    pub words: Vec<&'a OcrWordResult>,
+}
+
+impl OcrLine<'_> {
+    pub fn text(&self) -> String {
+        self.words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ")
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OcrAnalysisResult {
+    /// Plain text rebuilt from the layout (lines, blank line between paragraphs)
+    pub text: String,
+    pub pages: Vec<OcrPage>,
     pub words: Vec<OcrWordResult>,
     pub avg_confidence: f32,
+    /// ISO 639-3 code of the detected language
     pub detected_language: Option<String>,
     pub language_confidence: Option<f64>,
+    /// Clockwise rotation applied before OCR to turn the page upright, in degrees
+    pub rotation: u32,
+    /// Skew corrected before OCR, in degrees (clockwise positive)
+    pub skew_angle: f32,
 }
 
 impl OcrEngine {
@@ -32,28 +112,76 @@ impl OcrEngine {
             psm,
             oem,
             verbose,
+            preprocessing: Preprocessing::default(),
         })
     }
 
+    /// Clean up images (binarize, crop borders, upscale, ...) before Tesseract sees them
+    pub fn with_preprocessing(mut self, preprocessing: Preprocessing) -> Self {
+        self.preprocessing = preprocessing;
+        self
+    }
+
+    /// DPI used for OCR (and for rasterizing PDF pages)
+    pub fn dpi(&self) -> u32 {
+        self.dpi
+    }
+
+    /// Run Tesseract once (TSV output) and derive text, word confidences
+    /// and language from the same pass
     pub fn extract_with_confidence(&self, image_path: &Path)
                                    -> Result<OcrAnalysisResult, Box<dyn Error>>
     {
-        let output = Command::new("tesseract")
-            .arg(image_path)
-            .arg("stdout")
-            .arg("-l").arg(&self.language)
-            .arg("--dpi").arg(self.dpi.to_string())
-            .arg("tsv")
-            .output()?;
+        let (mut rotation, mut skew_angle) = (0, 0.0);
+        let (pages, words) = if self.preprocessing.is_enabled() {
+            let image = crate::utils::open_image(image_path)?;
+            let prepared = self.preprocessing.apply(image, image_path, self.dpi)?;
+            rotation = prepared.rotation;
+            skew_angle = prepared.skew;
+            let file = tempfile::Builder::new().prefix("ocr_preprocessed_").suffix(".png").tempfile()?;
+            prepared.image.save(file.path())?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Tesseract TSV failed: {}", stderr).into());
-        }
+            // Boxes are reported in the coordinates of the original image
+            let (pages, mut words) = self.run_tesseract(file.path())?;
+            for word in &mut words {
+                word.bbox = prepared.to_original(&word.bbox);
+            }
+            let pages = pages
+                .into_iter()
+                .map(|page| OcrPage { width: prepared.width, height: prepared.height, ..page })
+                .collect();
+            (pages, words)
+        } else if crate::utils::image_orientation(image_path) != Orientation::NoTransforms {
+            // Tesseract ignores EXIF orientation; OCR the upright image
+            let file = tempfile::Builder::new().prefix("ocr_upright_").suffix(".png").tempfile()?;
+            crate::utils::open_image(image_path)?.save(file.path())?;
+            self.run_tesseract(file.path())?
+        } else {
+            self.run_tesseract(image_path)?
+        };
 
-        let tsv = String::from_utf8(output.stdout)?;
-        let words = parse_tsv_output(&tsv)?;
+        Ok(self.analysis(pages, words, rotation, skew_angle))
+    }
+
+    /// OCR every page of a multi-page image in one Tesseract run, one result per page.
+    /// Used for frames the image decoder cannot read (e.g. CCITT fax TIFFs), so
+    /// preprocessing and EXIF orientation are not applied.
+    pub fn extract_pages(&self, image_path: &Path) -> Result<Vec<OcrAnalysisResult>, Box<dyn Error>> {
+        let (pages, words) = self.run_tesseract(image_path)?;
+
+        Ok(pages
+            .into_iter()
+            .map(|page| {
+                let page_words = words.iter().filter(|w| w.page_num == page.page_num).cloned().collect();
+                self.analysis(vec![page], page_words, 0, 0.0)
+            })
+            .collect())
+    }
 
+    /// Text, average confidence and language of Tesseract's pages and words
+    fn analysis(&self, pages: Vec<OcrPage>, words: Vec<OcrWordResult>, rotation: u32, skew_angle: f32)
+                -> OcrAnalysisResult
+    {
         // Calculate average confidence
         let avg_confidence = if !words.is_empty() {
             words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32
@@ -61,57 +189,56 @@ impl OcrEngine {
             0.0
         };
 
+        let mut result = OcrAnalysisResult {
+            text: String::new(),
+            pages,
+            words,
+            avg_confidence,
+            detected_language: None,
+            language_confidence: None,
+            rotation,
+            skew_angle,
+        };
+        result.text = result.layout_text();
+
         // Language detection from full text
-        let full_text: String = words.iter()
-            .map(|w| w.text.as_str())
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        let (detected_language, language_confidence) = if !full_text.is_empty() {
-            match whatlang::detect(&full_text) {
-                Some(info) => {
-                    let lang_code = format!("{:?}", info.lang());
-                    let confidence = info.confidence();
-
-                    if self.verbose {
-                        eprintln!("🌍 Detected: {} ({:.1}%)", lang_code, confidence * 100.0);
-                    }
-
-                    (Some(lang_code), Some(confidence))
-                }
-                None => (None, None),
+        if !result.text.is_empty()
+            && let Some(info) = whatlang::detect(&result.text)
+        {
+            // ISO 639-3 ("ukr", "eng"), as hOCR and ALTO expect
+            let lang_code = info.lang().code().to_string();
+            let confidence = info.confidence();
+
+            if self.verbose {
+                eprintln!("🌍 Detected: {} ({:.1}%)", lang_code, confidence * 100.0);
             }
-        } else {
-            (None, None)
-        };
 
-        Ok(OcrAnalysisResult {
-            words,
-            avg_confidence,
-            detected_language,
-            language_confidence,
-        })
+            result.detected_language = Some(lang_code);
+            result.language_confidence = Some(confidence);
+        }
+
+        result
     }
 
-    pub fn extract_text_from_image(&self, image_path: &Path) -> Result<String, Box<dyn Error>> {
+    /// Tesseract TSV output for one image, parsed into page sizes and words
+    fn run_tesseract(&self, image_path: &Path) -> Result<(Vec<OcrPage>, Vec<OcrWordResult>), Box<dyn Error>> {
         let mut cmd = Command::new("tesseract");
         cmd.arg(image_path)
             .arg("stdout")
             .arg("-l").arg(&self.language)
             .arg("--dpi").arg(self.dpi.to_string())
             .arg("--psm").arg(self.psm.to_string())
-            .arg("--oem").arg(self.oem.to_string());
+            .arg("--oem").arg(self.oem.to_string())
+            .arg("tsv");
 
         if self.verbose {
-            eprintln!("🔧 Tesseract: tesseract {} stdout -l {} --dpi {} --psm {} --oem {}",
+            eprintln!("🔧 Tesseract: tesseract {} stdout -l {} --dpi {} --psm {} --oem {} tsv",
                       image_path.display(),
                       self.language,
                       self.dpi,
                       self.psm,
                       self.oem
             );
-        } else {
-            cmd.stderr(Stdio::null());
         }
 
         let output = cmd.output()?;
@@ -121,7 +248,43 @@ impl OcrEngine {
             return Err(format!("Tesseract failed: {}", stderr).into());
         }
 
-        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+        if self.verbose {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let tsv = String::from_utf8(output.stdout)?;
+        parse_tsv_output(&tsv)
+    }
+
+    /// Clockwise rotation (0, 90, 180, 270) that turns the page upright, from
+    /// Tesseract orientation and script detection (needs osd.traineddata)
+    pub fn detect_orientation(image_path: &Path, dpi: u32) -> Result<u32, Box<dyn Error>> {
+        let output = Command::new("tesseract")
+            .arg(image_path)
+            .arg("stdout")
+            .arg("--dpi").arg(dpi.to_string())
+            .arg("--psm").arg("0")
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Tesseract OSD failed: {}", stderr.trim()).into());
+        }
+
+        // "Rotate: 90" and "Orientation confidence: 15.3" lines
+        let osd = String::from_utf8(output.stdout)?;
+        let value = |name: &str| {
+            osd.lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|v| v.trim_start_matches(':').trim().parse::<f32>().ok())
+        };
+        let rotation = value("Rotate").ok_or("No rotation in OSD output")?;
+        let confidence = value("Orientation confidence").unwrap_or(0.0);
+
+        if confidence < MIN_ORIENTATION_CONFIDENCE {
+            return Ok(0);
+        }
+        Ok(rotation as u32 % 360)
     }
 
     /// Check available Tesseract languages
@@ -172,23 +335,171 @@ impl OcrEngine {
     }
 }
 
-fn parse_tsv_output(tsv: &str) -> Result<Vec<OcrWordResult>, Box<dyn Error>> {
+impl OcrAnalysisResult {
+    /// Group words into text lines; blocks and paragraphs can be rebuilt
+    /// from the lines' `block_num`/`par_num`
+    pub fn lines(&self) -> Vec<OcrLine<'_>> {
+        let mut lines: Vec<OcrLine> = Vec::new();
+
+        for word in &self.words {
+            match lines.last_mut() {
+                Some(line)
+                    if line.page_num == word.page_num
+                        && line.block_num == word.block_num
+                        && line.par_num == word.par_num
+                        && line.line_num == word.line_num =>
+                {
+                    line.bbox = line.bbox.union(&word.bbox);
+                    line.words.push(word);
+                }
+                _ => lines.push(OcrLine {
+                    page_num: word.page_num,
+                    block_num: word.block_num,
+                    par_num: word.par_num,
+                    line_num: word.line_num,
+                    bbox: word.bbox,
+                    words: vec![word],
+                }),
+            }
+        }
+
+        lines
+    }
+
+    /// Rebuild plain text the way Tesseract's txt renderer lays it out
+    fn layout_text(&self) -> String {
+        let mut text = String::new();
+        let mut previous: Option<(u32, u32, u32)> = None;
+
+        for line in self.lines() {
+            let paragraph = (line.page_num, line.block_num, line.par_num);
+            match previous {
+                Some(p) if p == paragraph => text.push('\n'),
+                Some(_) => text.push_str("\n\n"),
+                None => {}
+            }
+            text.push_str(&line.text());
+            previous = Some(paragraph);
+        }
+
+        text
+    }
+}
+
+/// Parse Tesseract TSV into page sizes (level 1) and words (level 5).
+///
+/// Columns: level, page_num, block_num, par_num, line_num, word_num,
+/// left, top, width, height, conf, text
+fn parse_tsv_output(tsv: &str) -> Result<(Vec<OcrPage>, Vec<OcrWordResult>), Box<dyn Error>> {
+    let mut pages = Vec::new();
     let mut words = Vec::new();
 
     for line in tsv.lines().skip(1) {
         let cols: Vec<&str> = line.split('\t').collect();
-        if cols.len() >= 12 && cols[0] == "5" {
-            let confidence = cols[10].parse::<f32>().unwrap_or(0.0);
-            let text = cols[11].to_string();
-
-            if !text.is_empty() {
-                words.push(OcrWordResult {
-                    text,
-                    confidence,
-                });
+        if cols.len() < 10 {
+            continue;
+        }
+
+        let num = |i: usize| cols[i].trim().parse::<u32>().unwrap_or(0);
+        let bbox = BoundingBox {
+            left: num(6),
+            top: num(7),
+            width: num(8),
+            height: num(9),
+        };
+
+        match cols[0] {
+            "1" => pages.push(OcrPage {
+                page_num: num(1),
+                width: bbox.width,
+                height: bbox.height,
+            }),
+            "5" if cols.len() >= 12 => {
+                let confidence = cols[10].parse::<f32>().unwrap_or(0.0);
+                let text = cols[11].trim().to_string();
+
+                if !text.is_empty() {
+                    words.push(OcrWordResult {
+                        text,
+                        confidence,
+                        page_num: num(1),
+                        block_num: num(2),
+                        par_num: num(3),
+                        line_num: num(4),
+                        word_num: num(5),
+                        bbox,
+                    });
+                }
             }
+            _ => {}
+        }
+    }
+
+    Ok((pages, words))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TSV: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t200\t100\t-1\t
+2\t1\t1\t0\t0\t0\t10\t10\t150\t70\t-1\t
+3\t1\t1\t1\t0\t0\t10\t10\t95\t32\t-1\t
+4\t1\t1\t1\t1\t0\t10\t10\t85\t12\t-1\t
+5\t1\t1\t1\t1\t1\t10\t10\t40\t12\t95.5\tHello
+5\t1\t1\t1\t1\t2\t55\t10\t40\t12\t90\tworld
+4\t1\t1\t1\t2\t0\t10\t30\t30\t12\t-1\t
+5\t1\t1\t1\t2\t1\t10\t30\t30\t12\t88\tnext
+3\t1\t1\t2\t0\t0\t10\t60\t50\t12\t-1\t
+4\t1\t1\t2\t1\t0\t10\t60\t50\t12\t-1\t
+5\t1\t1\t2\t1\t1\t10\t60\t50\t12\t80\tPara
+5\t1\t1\t2\t1\t2\t70\t60\t5\t12\t-1\t 
+";
+
+    fn analysis(pages: Vec<OcrPage>, words: Vec<OcrWordResult>) -> OcrAnalysisResult {
+        OcrAnalysisResult {
+            text: String::new(),
+            pages,
+            words,
+            avg_confidence: 0.0,
+            detected_language: None,
+            language_confidence: None,
+            rotation: 0,
+            skew_angle: 0.0,
         }
     }
 
-    Ok(words)
+    #[test]
+    fn tsv_keeps_page_sizes_and_word_hierarchy() {
+        let (pages, words) = parse_tsv_output(TSV).unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!((pages[0].page_num, pages[0].width, pages[0].height), (1, 200, 100));
+
+        // Blank words are dropped
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, ["Hello", "world", "next", "Para"]);
+
+        let world = &words[1];
+        assert_eq!(
+            (world.page_num, world.block_num, world.par_num, world.line_num, world.word_num),
+            (1, 1, 1, 1, 2)
+        );
+        assert_eq!(world.bbox, BoundingBox { left: 55, top: 10, width: 40, height: 12 });
+        assert_eq!(world.confidence, 90.0);
+    }
+
+    #[test]
+    fn layout_text_breaks_lines_and_paragraphs() {
+        let (pages, words) = parse_tsv_output(TSV).unwrap();
+        let result = analysis(pages, words);
+
+        let lines = result.lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].text(), "Hello world");
+        assert_eq!(lines[0].bbox, BoundingBox { left: 10, top: 10, width: 85, height: 12 });
+
+        assert_eq!(result.layout_text(), "Hello world\nnext\n\nPara");
+    }
 }
@@ -0,0 +1,780 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::archive::{self, ExtractionBudget};
+
+/// Read a text entry (XML part) of an OOXML/ODF package, if present.
+/// Parts over the extraction budget are not read; `budget.check()` tells why.
+pub fn read_package_part<R: Read + Seek>(
+    package: &mut zip::ZipArchive<R>,
+    name: &str,
+    budget: &ExtractionBudget,
+) -> Option<String> {
+    String::from_utf8(read_package_bytes(package, name, budget)?).ok()
+}
+
+/// Read a binary package entry (embedded media) within the extraction budget
+pub fn read_package_bytes<R: Read + Seek>(
+    package: &mut zip::ZipArchive<R>,
+    name: &str,
+    budget: &ExtractionBudget,
+) -> Option<Vec<u8>> {
+    let mut file = package.by_name(name).ok()?;
+    let compressed_size = file.compressed_size();
+    archive::read_limited(&mut file, compressed_size, budget).ok()
+}
+
+/// Names of package entries matching `predicate`, in natural order
+/// (`header2.xml` before `header10.xml`)
+pub fn package_part_names<R: Read + Seek>(
+    package: &zip::ZipArchive<R>,
+    predicate: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut names: Vec<String> = package
+        .file_names()
+        .filter(|name| predicate(name))
+        .map(str::to_string)
+        .collect();
+
+    names.sort_by_key(|name| {
+        let stem: String = name.chars().filter(|c| !c.is_ascii_digit()).collect();
+        let digits: String = name.chars().filter(|c| c.is_ascii_digit()).collect();
+        (stem, digits.parse::<u64>().unwrap_or(0))
+    });
+    names
+}
+
+/// Which text box (`w:txbxContent`) content a WordprocessingML walk keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextBoxes {
+    Include,
+    Skip,
+    Only,
+}
+
+/// Plain text of a WordprocessingML part (body, header, footer, notes, comments).
+/// DrawingML text (`a:p`/`a:r`/`a:t`, `a:tbl`) uses the same local names and
+/// is handled as well.
+/// Paragraphs end with '\n'; table cells end with '\t' and rows with '\n', while
+/// nested tables are flattened into their outer cell with spaces.
+/// Hyperlink runs are kept, tabs and breaks become '\t'/'\n'.
+pub fn wordml_text(xml: &str, text_boxes: TextBoxes) -> Result<String, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut text = String::new();
+
+    let mut in_text = false;
+    let mut run_depth = 0usize;
+    let mut cell_depth = 0usize;
+    let mut table_depth = 0usize;
+    let mut text_box_depth = 0usize;
+    // mc:Fallback repeats mc:Choice content (e.g. VML copies of text boxes)
+    let mut fallback_depth = 0usize;
+
+    loop {
+        let keep = |text_box_depth: usize| match text_boxes {
+            TextBoxes::Include => true,
+            TextBoxes::Skip => text_box_depth == 0,
+            TextBoxes::Only => text_box_depth > 0,
+        };
+
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name();
+                if name.as_ref() == b"Fallback" || fallback_depth > 0 {
+                    fallback_depth += 1;
+                    continue;
+                }
+
+                match name.as_ref() {
+                    b"t" => in_text = true,
+                    b"r" => run_depth += 1,
+                    b"tc" => cell_depth += 1,
+                    b"tbl" => table_depth += 1,
+                    b"txbxContent" => text_box_depth += 1,
+                    _ => {}
+                }
+            }
+            Event::Empty(e) => {
+                if fallback_depth > 0 || !keep(text_box_depth) {
+                    continue;
+                }
+
+                // w:tab also appears in paragraph properties (tab stops), only runs count;
+                // DrawingML a:br sits between runs
+                match e.local_name().as_ref() {
+                    b"tab" | b"ptab" if run_depth > 0 => text.push('\t'),
+                    b"br" | b"cr" => text.push('\n'),
+                    _ => {}
+                }
+            }
+            Event::Text(e) if in_text && fallback_depth == 0 && keep(text_box_depth) => {
+                text.push_str(&e.unescape()?);
+            }
+            Event::End(e) => {
+                if fallback_depth > 0 {
+                    fallback_depth -= 1;
+                    continue;
+                }
+
+                let kept = keep(text_box_depth);
+                match e.local_name().as_ref() {
+                    b"t" => in_text = false,
+                    b"r" => run_depth = run_depth.saturating_sub(1),
+                    b"p" if kept => text.push(if cell_depth > 0 { ' ' } else { '\n' }),
+                    b"tc" => {
+                        cell_depth = cell_depth.saturating_sub(1);
+                        if kept {
+                            // Drop the separator after the cell's last paragraph
+                            text.truncate(text.trim_end_matches(' ').len());
+                            text.push(if table_depth > 1 { ' ' } else { '\t' });
+                        }
+                    }
+                    b"tr" if kept => text.push(if table_depth > 1 { ' ' } else { '\n' }),
+                    b"tbl" => table_depth = table_depth.saturating_sub(1),
+                    b"txbxContent" => text_box_depth = text_box_depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(text)
+}
+
+/// An internal relationship of a package part
+#[derive(Debug, Clone)]
+pub struct Relationship {
+    /// Last segment of the relationship type (`image`, `notesSlide`, `slide`)
+    pub kind: String,
+    /// Package path of the target
+    pub target: String,
+}
+
+/// Internal relationships of `part`, keyed by relationship id, read from its
+/// `_rels` part; empty when the part has none
+pub fn relationships<R: Read + Seek>(
+    package: &mut zip::ZipArchive<R>,
+    part: &str,
+    budget: &ExtractionBudget,
+) -> HashMap<String, Relationship> {
+    let (folder, file) = part.rsplit_once('/').unwrap_or(("", part));
+    let rels_name = format!("{}/_rels/{}.rels", folder, file);
+
+    read_package_part(package, rels_name.trim_start_matches('/'), budget)
+        .and_then(|xml| parse_relationships(part, &xml).ok())
+        .unwrap_or_default()
+}
+
+fn parse_relationships(
+    part: &str,
+    xml: &str,
+) -> Result<HashMap<String, Relationship>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut relationships = HashMap::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"Relationship" => {
+                // Hyperlinks and linked files live outside the package
+                let external = e
+                    .try_get_attribute("TargetMode")?
+                    .is_some_and(|mode| mode.value.as_ref() == b"External");
+                let id = e.try_get_attribute("Id")?;
+                let target = e.try_get_attribute("Target")?;
+                let kind = e.try_get_attribute("Type")?;
+
+                if let (false, Some(id), Some(target)) = (external, id, target) {
+                    let kind = match kind {
+                        Some(kind) => kind.unescape_value()?.into_owned(),
+                        None => String::new(),
+                    };
+                    relationships.insert(
+                        id.unescape_value()?.into_owned(),
+                        Relationship {
+                            kind: kind.rsplit('/').next().unwrap_or_default().to_string(),
+                            target: resolve_target(part, &target.unescape_value()?),
+                        },
+                    );
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(relationships)
+}
+
+/// Relationship ids of images referenced by a part, in document order
+/// (`a:blip r:embed` for DrawingML, `v:imagedata r:id` for legacy VML)
+pub fn image_references(xml: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    references(xml, |element| match element {
+        b"blip" => Some(b"embed"),
+        b"imagedata" => Some(b"id"),
+        _ => None,
+    })
+}
+
+/// Relationship ids of the slides of `ppt/presentation.xml`, in slide order
+pub fn slide_references(xml: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    references(xml, |element| match element {
+        b"sldId" => Some(b"id"),
+        _ => None,
+    })
+}
+
+/// Distinct `r:*` attribute values, in document order; `attribute_of` names the
+/// relationship attribute for the elements of interest
+fn references(
+    xml: &str,
+    attribute_of: impl Fn(&[u8]) -> Option<&'static [u8]>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut ids = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => {
+                let Some(attribute) = attribute_of(e.local_name().as_ref()) else {
+                    continue;
+                };
+
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    if attr.key.local_name().as_ref() == attribute
+                        && attr.key.prefix().is_some_and(|p| p.as_ref() == b"r")
+                    {
+                        let id = attr.unescape_value()?.into_owned();
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Package path of a relationship target of `part`
+/// (`word/document.xml` + `media/image1.png` → `word/media/image1.png`)
+fn resolve_target(part: &str, target: &str) -> String {
+    // Absolute targets start at the package root, relative ones at the part's folder
+    let mut components: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => {
+            let mut folder: Vec<&str> = part.split('/').collect();
+            folder.pop();
+            folder
+        }
+    };
+
+    for component in target.trim_start_matches('/').split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Longest run of spaces a single `<text:s text:c=…>` may expand to
+const MAX_ODF_SPACES: usize = 1_000;
+
+/// Plain text of an OpenDocument `content.xml` (ODT). Paragraphs and headings
+/// end with '\n'; table cells end with '\t' and rows with '\n', like DOCX.
+pub fn odf_text(xml: &str) -> Result<String, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut text = String::new();
+
+    let mut paragraph_depth = 0usize;
+    let mut cell_depth = 0usize;
+    let mut table_depth = 0usize;
+    // Deleted text of tracked changes, comments and other non-content subtrees
+    let mut skip_depth = 0usize;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name();
+                if skip_depth > 0
+                    || matches!(
+                        name.as_ref(),
+                        b"tracked-changes" | b"note-citation" | b"annotation" | b"annotation-end"
+                    )
+                {
+                    skip_depth += 1;
+                    continue;
+                }
+
+                match name.as_ref() {
+                    b"p" | b"h" => paragraph_depth += 1,
+                    b"table-cell" => cell_depth += 1,
+                    b"table" => table_depth += 1,
+                    _ => {}
+                }
+            }
+            Event::Empty(e) => {
+                if skip_depth > 0 {
+                    continue;
+                }
+
+                match e.local_name().as_ref() {
+                    // <text:s text:c="3"/> is a run of spaces
+                    b"s" if paragraph_depth > 0 => {
+                        // The count comes from the file; an unparsable one is a single space
+                        let count = match e.try_get_attribute("text:c")? {
+                            Some(count) => count.unescape_value()?.parse().unwrap_or(1),
+                            None => 1,
+                        }
+                        .min(MAX_ODF_SPACES);
+                        text.push_str(&" ".repeat(count));
+                    }
+                    b"tab" if paragraph_depth > 0 => text.push('\t'),
+                    b"line-break" if paragraph_depth > 0 => text.push('\n'),
+                    b"p" | b"h" if cell_depth == 0 => text.push('\n'),
+                    b"table-cell" | b"covered-table-cell" => {
+                        // Trailing empty cells are stored as a single repeated cell; one is enough
+                        text.push(if table_depth > 1 { ' ' } else { '\t' })
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(e) if paragraph_depth > 0 && skip_depth == 0 => {
+                text.push_str(&e.unescape()?);
+            }
+            Event::End(e) => {
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                    continue;
+                }
+
+                match e.local_name().as_ref() {
+                    b"p" | b"h" => {
+                        paragraph_depth = paragraph_depth.saturating_sub(1);
+                        if paragraph_depth == 0 {
+                            text.push(if cell_depth > 0 { ' ' } else { '\n' });
+                        }
+                    }
+                    b"table-cell" => {
+                        cell_depth = cell_depth.saturating_sub(1);
+                        text.truncate(text.trim_end_matches(' ').len());
+                        text.push(if table_depth > 1 { ' ' } else { '\t' });
+                    }
+                    b"table-row" => text.push(if table_depth > 1 { ' ' } else { '\n' }),
+                    b"table" => table_depth = table_depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(text)
+}
+
+/// Page count saved in an OpenDocument `meta.xml` (`meta:document-statistic`)
+pub fn odf_page_count(xml: &str) -> Option<usize> {
+    let mut reader = Reader::from_str(xml);
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"document-statistic" => {
+                let count = e.try_get_attribute("meta:page-count").ok()??;
+                return count.unescape_value().ok()?.parse().ok();
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// A text-bearing shape (`p:sp`) or table frame (`p:graphicFrame`) of a slide
+#[derive(Debug, Clone)]
+pub struct SlideShape {
+    /// Placeholder type (`title`, `ctrTitle`, `body`, `sldNum`, ...); `obj` for
+    /// placeholders without a type, `None` for free shapes
+    pub placeholder: Option<String>,
+    pub text: String,
+}
+
+impl SlideShape {
+    pub fn is_title(&self) -> bool {
+        matches!(self.placeholder.as_deref(), Some("title" | "ctrTitle"))
+    }
+
+    /// Slide number, date and footer placeholders, repeated on every slide
+    pub fn is_boilerplate(&self) -> bool {
+        matches!(self.placeholder.as_deref(), Some("sldNum" | "dt" | "ftr"))
+    }
+}
+
+/// Shapes of a PresentationML slide (or notes slide) in drawing order,
+/// including those inside groups
+pub fn slide_shapes(xml: &str) -> Result<Vec<SlideShape>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut shapes = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if matches!(e.local_name().as_ref(), b"sp" | b"graphicFrame") => {
+                let span = reader.read_to_end(e.name())?;
+                let inner = &xml[span.start as usize..span.end as usize];
+
+                let text = wordml_text(inner, TextBoxes::Include)?;
+                if !text.trim().is_empty() {
+                    shapes.push(SlideShape {
+                        placeholder: placeholder_type(inner)?,
+                        text,
+                    });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(shapes)
+}
+
+fn placeholder_type(shape_xml: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(shape_xml);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"ph" => {
+                return Ok(Some(match e.try_get_attribute("type")? {
+                    Some(kind) => kind.unescape_value()?.into_owned(),
+                    None => "obj".to_string(),
+                }));
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Number format codes of the formatted cells of every XLSX worksheet, keyed by
+/// sheet name and 0-based (row, column). Cells in the General format are left out.
+pub fn xlsx_number_formats<R: Read + Seek>(
+    package: &mut zip::ZipArchive<R>,
+    budget: &ExtractionBudget,
+) -> HashMap<String, HashMap<(u32, u32), String>> {
+    let mut formats = HashMap::new();
+    let Some(styles) = read_package_part(package, "xl/styles.xml", budget)
+        .and_then(|xml| cell_style_formats(&xml).ok())
+        .filter(|styles| styles.iter().any(Option::is_some))
+    else {
+        return formats;
+    };
+    let Some(workbook) = read_package_part(package, "xl/workbook.xml", budget) else {
+        return formats;
+    };
+    let workbook_rels = relationships(package, "xl/workbook.xml", budget);
+
+    for (name, id) in workbook_sheets(&workbook).unwrap_or_default() {
+        let Some(sheet_xml) = workbook_rels
+            .get(&id)
+            .and_then(|relationship| read_package_part(package, &relationship.target, budget))
+        else {
+            continue;
+        };
+
+        formats.insert(name, cell_formats(&sheet_xml, &styles).unwrap_or_default());
+    }
+
+    formats
+}
+
+/// Number format code of every cell style (`cellXfs/xf`) of `xl/styles.xml`, by
+/// style index; `None` for General and for built-in date formats, which calamine
+/// already reads as dates
+fn cell_style_formats(xml: &str) -> Result<Vec<Option<String>>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut custom = HashMap::new();
+    let mut styles = Vec::new();
+    let mut in_cell_styles = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"cellXfs" => in_cell_styles = true,
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"numFmt" => {
+                    if let (Some(id), Some(code)) =
+                        (e.try_get_attribute("numFmtId")?, e.try_get_attribute("formatCode")?)
+                    {
+                        custom.insert(id.unescape_value()?.into_owned(), code.unescape_value()?.into_owned());
+                    }
+                }
+                b"xf" if in_cell_styles => {
+                    let id = match e.try_get_attribute("numFmtId")? {
+                        Some(id) => id.unescape_value()?.into_owned(),
+                        None => "0".to_string(),
+                    };
+                    let code = custom.get(&id).cloned().or_else(|| builtin_number_format(&id).map(str::to_string));
+                    styles.push(code);
+                }
+                _ => {}
+            },
+            Event::End(e) if e.local_name().as_ref() == b"cellXfs" => in_cell_styles = false,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(styles)
+}
+
+/// Built-in number formats (ECMA-376 18.8.30) that are not dates
+fn builtin_number_format(id: &str) -> Option<&'static str> {
+    Some(match id {
+        "1" => "0",
+        "2" => "0.00",
+        "3" => "#,##0",
+        "4" => "#,##0.00",
+        "9" => "0%",
+        "10" => "0.00%",
+        "11" => "0.00E+00",
+        "37" => "#,##0 ;(#,##0)",
+        "38" => "#,##0 ;[Red](#,##0)",
+        "39" => "#,##0.00;(#,##0.00)",
+        "40" => "#,##0.00;[Red](#,##0.00)",
+        "48" => "##0.0E+0",
+        _ => return None,
+    })
+}
+
+/// Sheets of `xl/workbook.xml` as (name, relationship id), in workbook order
+fn workbook_sheets(xml: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut sheets = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"sheet" => {
+                let name = e.try_get_attribute("name")?;
+                let id = e
+                    .attributes()
+                    .filter_map(Result::ok)
+                    .find(|attr| attr.key.local_name().as_ref() == b"id");
+                if let (Some(name), Some(id)) = (name, id) {
+                    sheets.push((name.unescape_value()?.into_owned(), id.unescape_value()?.into_owned()));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(sheets)
+}
+
+/// Number format code of every cell of a worksheet whose style (`c r="B3" s="2"`)
+/// has one in `styles`, at its 0-based (row, column)
+fn cell_formats(xml: &str, styles: &[Option<String>]) -> Result<HashMap<(u32, u32), String>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut cells = HashMap::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"c" => {
+                let (Some(reference), Some(style)) = (e.try_get_attribute("r")?, e.try_get_attribute("s")?) else {
+                    continue;
+                };
+                let position = cell_position(&reference.unescape_value()?);
+                let format = style
+                    .unescape_value()?
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|style| styles.get(style)?.clone());
+                if let (Some(position), Some(format)) = (position, format) {
+                    cells.insert(position, format);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(cells)
+}
+
+/// "B3" → (2, 1)
+fn cell_position(reference: &str) -> Option<(u32, u32)> {
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = reference.split_at(split);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+
+    let column = letters.chars().try_fold(0u32, |column, c| {
+        column.checked_mul(26)?.checked_add(c as u32 - 'A' as u32 + 1)
+    })?;
+    let row = digits.parse::<u32>().ok()?;
+    Some((row.checked_sub(1)?, column - 1))
+}
+
+/// Document properties of an OOXML package: statistics from `docProps/app.xml`
+/// and authorship from `docProps/core.xml`. Missing or empty values are left out.
+pub fn document_properties(path: &Path, budget: &ExtractionBudget) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    let Some(mut package) = fs::File::open(path)
+        .ok()
+        .and_then(|file| zip::ZipArchive::new(file).ok())
+    else {
+        return properties;
+    };
+
+    let parts: [(&str, PropertyFields); 2] = [
+        (
+            "docProps/app.xml",
+            &[
+                (b"Pages", "pages"),
+                (b"Slides", "slides"),
+                (b"Words", "words"),
+                (b"Characters", "characters"),
+            ],
+        ),
+        (
+            "docProps/core.xml",
+            &[
+                (b"title", "title"),
+                (b"creator", "author"),
+                (b"created", "created"),
+                (b"modified", "last_modified"),
+                (b"lastModifiedBy", "last_modified_by"),
+            ],
+        ),
+    ];
+
+    for (part, fields) in parts {
+        let Some(xml) = read_package_part(&mut package, part, budget) else {
+            continue;
+        };
+        for (element, value) in element_texts(&xml).unwrap_or_default() {
+            if let Some((_, key)) = fields.iter().find(|(name, _)| *name == element.as_slice())
+                && !value.trim().is_empty()
+            {
+                properties.insert(key.to_string(), value.trim().to_string());
+            }
+        }
+    }
+
+    properties
+}
+
+/// Property elements (local name) and the metadata keys they are reported under
+type PropertyFields = &'static [(&'static [u8], &'static str)];
+
+/// Local name of a leaf element and its text
+type ElementText = (Vec<u8>, String);
+
+/// Text content of every leaf element, keyed by local name
+fn element_texts(xml: &str) -> Result<Vec<ElementText>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut values = Vec::new();
+    let mut current: Option<ElementText> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => current = Some((e.local_name().as_ref().to_vec(), String::new())),
+            Event::Text(e) => {
+                if let Some((_, value)) = current.as_mut() {
+                    value.push_str(&e.unescape()?);
+                }
+            }
+            Event::End(_) => values.extend(current.take()),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveLimits;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn odf_comments_are_not_document_text() {
+        let xml = r#"<office:text><text:p>Total <office:annotation office:name="c1"><dc:creator>Ann</dc:creator>
+            <text:p>check this</text:p></office:annotation>due<office:annotation-end office:name="c1"/> today</text:p>
+            <text:tracked-changes><text:changed-region><text:deletion><text:p>old</text:p></text:deletion></text:changed-region></text:tracked-changes>
+            </office:text>"#;
+        assert_eq!(odf_text(xml).unwrap(), "Total due today\n");
+    }
+
+    #[test]
+    fn odf_space_runs_are_bounded() {
+        let xml = r#"<office:text><text:p>a<text:s text:c="3"/>b<text:s text:c="18446744073709551615"/>c<text:s text:c="-2"/>d<text:s text:c="x"/>e<text:s/>f</text:p></office:text>"#;
+        let expected = format!("a   b{}c d e f\n", " ".repeat(MAX_ODF_SPACES));
+        assert_eq!(odf_text(xml).unwrap(), expected);
+    }
+
+    #[test]
+    fn docx_table_cells_have_no_trailing_blanks() {
+        let xml = r#"<w:body><w:tbl><w:tr>
+            <w:tc><w:p><w:r><w:t>No.</w:t></w:r></w:p></w:tc>
+            <w:tc><w:p><w:r><w:t>A-17</w:t></w:r></w:p><w:p><w:r><w:t>rev 2</w:t></w:r></w:p></w:tc>
+            <w:tc><w:tbl><w:tr><w:tc><w:p><w:r><w:t>x</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>y</w:t></w:r></w:p></w:tc></w:tr></w:tbl></w:tc>
+            </w:tr></w:tbl></w:body>"#;
+        assert_eq!(wordml_text(xml, TextBoxes::Include).unwrap(), "No.\tA-17 rev 2\tx y\t\n");
+    }
+
+    #[test]
+    fn xlsx_number_formats_by_sheet_and_cell() {
+        let parts = [
+            (
+                "xl/styles.xml",
+                r#"<styleSheet><numFmts count="1"><numFmt numFmtId="164" formatCode="0.000"/></numFmts>
+                <cellStyleXfs count="1"><xf numFmtId="10"/></cellStyleXfs>
+                <cellXfs count="4"><xf numFmtId="0"/><xf numFmtId="9"/><xf numFmtId="164"/><xf numFmtId="14"/></cellXfs>
+                </styleSheet>"#,
+            ),
+            (
+                "xl/workbook.xml",
+                r#"<workbook xmlns:r="r"><sheets><sheet name="Q1" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships><Relationship Id="rId1" Type="t/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><sheetData><row r="1"><c r="A1" s="1"><v>0.5</v></c><c r="B1"><v>1</v></c></row>
+                <row r="12"><c r="AB12" s="2"><v>2</v></c><c r="C12" s="3"><v>45000</v></c><c r="D12" s="0"/></row>
+                </sheetData></worksheet>"#,
+            ),
+        ];
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, xml) in parts {
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(xml.as_bytes()).unwrap();
+        }
+        let mut package = zip::ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let formats = xlsx_number_formats(&mut package, &ExtractionBudget::new(ArchiveLimits::default()));
+        let sheet = &formats["Q1"];
+        assert_eq!(sheet.len(), 2);
+        assert_eq!(sheet[&(0, 0)], "0%");
+        assert_eq!(sheet[&(11, 27)], "0.000");
+    }
+}
@@ -1,13 +1,17 @@
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::ocr_engine::{BoundingBox, OcrAnalysisResult};
+use crate::utils::open_image;
+
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 pub(crate) enum PdfCreationMethod {
     /// Use ocrmypdf (Python) - best quality, requires installation
     #[value(name = "ocrmypdf")]
     OcrMyPdf,
-    /// Use native Rust (pdf_writer) - basic fallback, image-only PDF
+    /// Use native Rust (pdf_writer) - image with invisible text layer from OCR word boxes
     Native,
 }
 
@@ -16,25 +20,34 @@ pub fn check_ocrmypdf_installed() -> bool {
     which::which("ocrmypdf").is_ok()
 }
 
+/// One page of a searchable PDF: the page image and its OCR result
+pub struct PdfPage<'a> {
+    pub image: &'a Path,
+    pub ocr: Option<&'a OcrAnalysisResult>,
+}
+
 pub fn create_searchable_pdf(
-    image_path: &Path,
-    ocr_text: &str,
+    pages: &[PdfPage],
     output_path: &Path,
     language: &str,
     method: PdfCreationMethod,
 ) -> Result<(), Box<dyn Error>> {
     match method {
         PdfCreationMethod::OcrMyPdf => {
-            create_with_ocrmypdf(image_path, output_path, language)
+            let images: Vec<&Path> = pages.iter().map(|p| p.image).collect();
+            create_with_ocrmypdf(&images, output_path, language)
         }
         PdfCreationMethod::Native => {
-            create_with_pdf_writer(image_path, ocr_text, output_path)
+            create_with_pdf_writer(pages, output_path)
         }
     }
 }
 
+/// Resolution ocrmypdf is told the page images have
+const OCRMYPDF_DPI: u32 = 300;
+
 pub fn create_with_ocrmypdf(
-    image_path: &Path,
+    images: &[&Path],
     output_path: &Path,
     language: &str,
 ) -> Result<(), Box<dyn Error>> {
@@ -47,35 +60,45 @@ pub fn create_with_ocrmypdf(
             Or use --pdf-method native for Rust-based PDF creation\n\
             More info: https://ocrmypdf.readthedocs.io/en/latest/installation.html".to_string().into());
     }
+    let Some(first) = images.first() else {
+        return Err("No pages to convert".into());
+    };
 
     let temp_dir = std::env::temp_dir();
-    let temp_rgb = temp_dir.join(format!(
-        "ocr_temp_{}.png",
-        image_path.file_stem().unwrap().to_string_lossy()
-    ));
-
-    // Convert to RGB PNG
-    let img = image::open(image_path)?;
-    let rgb_img = img.to_rgb8();
-    image::save_buffer(
-        &temp_rgb,
-        &rgb_img,
-        rgb_img.width(),
-        rgb_img.height(),
-        image::ColorType::Rgb8,
-    )?;
-
-    let output = Command::new("ocrmypdf")
-        .arg("-l")
-        .arg(language)
-        .arg("--image-dpi")
-        .arg("300")
-        .arg(&temp_rgb)
+    let stem = first.file_stem().unwrap().to_string_lossy();
+
+    let mut cmd = Command::new("ocrmypdf");
+    cmd.arg("-l").arg(language);
+
+    let temp_input = if images.len() == 1 {
+        // Convert to RGB PNG
+        let temp_rgb = temp_dir.join(format!("ocr_temp_{}.png", stem));
+        let img = open_image(first)?;
+        let rgb_img = img.to_rgb8();
+        image::save_buffer(
+            &temp_rgb,
+            &rgb_img,
+            rgb_img.width(),
+            rgb_img.height(),
+            image::ColorType::Rgb8,
+        )?;
+        cmd.arg("--image-dpi").arg(OCRMYPDF_DPI.to_string());
+        temp_rgb
+    } else {
+        // ocrmypdf takes a single image; hand it the pages as an image-only PDF
+        let temp_pdf = temp_dir.join(format!("ocr_temp_{}.pdf", stem));
+        let pages: Vec<PdfPage> = images.iter().map(|&image| PdfPage { image, ocr: None }).collect();
+        write_pdf(&pages, &temp_pdf, 72.0 / OCRMYPDF_DPI as f32)?;
+        temp_pdf
+    };
+
+    let output = cmd
+        .arg(&temp_input)
         .arg(output_path)
         .stderr(std::process::Stdio::piped())
         .output()?;
 
-    let _ = std::fs::remove_file(&temp_rgb);
+    let _ = std::fs::remove_file(&temp_input);
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -85,88 +108,249 @@ pub fn create_with_ocrmypdf(
     Ok(())
 }
 
+/// Glyph width (in 1/1000 em) of every CID in the invisible text font
+const GLYPH_WIDTH: f32 = 500.0;
+
+/// Locate Tesseract's glyphless font (`pdf.ttf`, shipped in tessdata).
+/// It has no visible outlines, which is exactly what an invisible text layer needs.
+pub fn find_glyphless_font() -> Option<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+
+    if let Ok(prefix) = std::env::var("TESSDATA_PREFIX") {
+        let prefix = PathBuf::from(prefix);
+        candidates.push(prefix.join("pdf.ttf"));
+        candidates.push(prefix.join("tessdata").join("pdf.ttf"));
+    }
+
+    candidates.extend([
+        "/usr/share/tesseract-ocr/5/tessdata/pdf.ttf",
+        "/usr/share/tesseract-ocr/4.00/tessdata/pdf.ttf",
+        "/usr/share/tessdata/pdf.ttf",
+        "/usr/local/share/tessdata/pdf.ttf",
+        "/opt/homebrew/share/tessdata/pdf.ttf",
+        "C:\\Program Files\\Tesseract-OCR\\tessdata\\pdf.ttf",
+    ].iter().map(PathBuf::from));
+
+    candidates.into_iter().find(|p| p.is_file())
+}
+
+/// Encode text as 2-byte CIDs (Identity-H, CID = UTF-16 code unit).
+/// Characters outside the BMP become U+FFFD.
+fn encode_cids(text: &str, used: &mut BTreeMap<u16, char>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() * 2);
+
+    for c in text.chars() {
+        let c = if (c as u32) <= 0xFFFF { c } else { char::REPLACEMENT_CHARACTER };
+        let cid = c as u32 as u16;
+        used.insert(cid, c);
+        bytes.extend_from_slice(&cid.to_be_bytes());
+    }
+
+    bytes
+}
+
 pub fn create_with_pdf_writer(
-    image_path: &Path,
-    ocr_text: &str,
+    pages: &[PdfPage],
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    write_pdf(pages, output_path, 1.0)
+}
+
+/// One PDF page per image, each with an invisible text layer from its OCR
+/// result (when it has one). Page size is the image size times `points_per_pixel`.
+fn write_pdf(
+    pages: &[PdfPage],
     output_path: &Path,
+    points_per_pixel: f32,
 ) -> Result<(), Box<dyn Error>> {
     use pdf_writer::{Pdf, Rect, Content, Str, Name, Ref, Finish, Filter};
-    use pdf_writer::types::TextRenderingMode;
+    use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, TextRenderingMode, UnicodeCmap};
     use image::GenericImageView;
-
-    let img = image::open(image_path)?;
-    let (width, height) = img.dimensions();
-
-    // Convert to JPEG
-    let temp_dir = std::env::temp_dir();
-    let temp_jpg = temp_dir.join("temp_ocr.jpg");
-    img.save(&temp_jpg)?;
-    let img_data = std::fs::read(&temp_jpg)?;
-    let _ = std::fs::remove_file(&temp_jpg);
+    use image::codecs::jpeg::JpegEncoder;
+
+    // The text layer font must be embedded; a PDF referencing a missing
+    // "GlyphLessFont" renders differently in every viewer
+    let font_file = if pages.iter().any(|p| p.ocr.is_some()) {
+        let font_path = find_glyphless_font().ok_or(
+            "Glyphless font pdf.ttf not found in tessdata.\n\
+             Set TESSDATA_PREFIX to the directory that contains it, or use --pdf-method ocrmypdf",
+        )?;
+        Some(std::fs::read(font_path)?)
+    } else {
+        None
+    };
 
     let mut pdf = Pdf::new();
+    let mut next_id = Ref::new(1);
 
-    let catalog_id = Ref::new(1);
-    let page_tree_id = Ref::new(2);
-    let page_id = Ref::new(3);
-    let image_id = Ref::new(4);
-    let content_id = Ref::new(5);
-    let font_id = Ref::new(6);
+    let catalog_id = next_id.bump();
+    let page_tree_id = next_id.bump();
+    let font_id = next_id.bump();
+    let page_ids: Vec<Ref> = pages.iter().map(|_| next_id.bump()).collect();
 
     // Catalog
     pdf.catalog(catalog_id).pages(page_tree_id);
 
     // Page tree
-    pdf.pages(page_tree_id).kids([page_id]).count(1);
-
-    // Page
-    let mut page = pdf.page(page_id);
-    page.parent(page_tree_id);
-    page.media_box(Rect::new(0.0, 0.0, width as f32, height as f32));
-    page.contents(content_id);
-
-    let mut resources = page.resources();
-    resources.x_objects().pair(Name(b"Im1"), image_id);
-    resources.fonts().pair(Name(b"F1"), font_id);
-    resources.finish();
-    page.finish();
-
-    // Font
-    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
-
-    // Image
-    let mut image = pdf.image_xobject(image_id, &img_data);
-    image.width(width as i32);
-    image.height(height as i32);
-    image.color_space().device_rgb();
-    image.bits_per_component(8);
-    image.filter(Filter::DctDecode).finish();  // ✅ Додати .finish()
-    image.finish();
-
-    // Content: image + invisible text
-    let mut content = Content::new();
-
-    // Draw image
-    content.save_state();
-    content.transform([width as f32, 0.0, 0.0, height as f32, 0.0, 0.0]);
-    content.x_object(Name(b"Im1"));
-    content.restore_state();
-
-    // Add invisible text
-    content.begin_text();
-    content.set_text_rendering_mode(TextRenderingMode::Invisible);  // ✅ Правильний enum
-    content.set_font(Name(b"F1"), 12.0);
-    content.next_line(10.0, height as f32 - 20.0);
-
-    let escaped = ocr_text
-        .replace('\\', "\\\\")
-        .replace('(', "\\(")
-        .replace(')', "\\)");
-    content.show(Str(escaped.as_bytes()));  // ✅ Str() замість TextStr()
-
-    content.end_text();
-
-    pdf.stream(content_id, &content.finish());
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+
+    let mut used_cids = BTreeMap::new();
+
+    for (page_data, &page_id) in pages.iter().zip(&page_ids) {
+        let image_id = next_id.bump();
+        let content_id = next_id.bump();
+
+        let img = open_image(page_data.image)?;
+        let (original_width, original_height) = img.dimensions();
+
+        // Pages turned upright for OCR are embedded upright, so the text layer runs along the lines
+        let rotation = page_data.ocr.map_or(0, |ocr| ocr.rotation);
+        let img = match rotation {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+        let (width, height) = img.dimensions();
+        let (page_width, page_height) = (width as f32 * points_per_pixel, height as f32 * points_per_pixel);
+
+        // Encode as JPEG in memory
+        let mut img_data = Vec::new();
+        JpegEncoder::new_with_quality(&mut img_data, 90).encode_image(&img.to_rgb8())?;
+
+        // Page
+        let mut page = pdf.page(page_id);
+        page.parent(page_tree_id);
+        page.media_box(Rect::new(0.0, 0.0, page_width, page_height));
+        page.contents(content_id);
+
+        let mut resources = page.resources();
+        resources.x_objects().pair(Name(b"Im1"), image_id);
+        if font_file.is_some() {
+            resources.fonts().pair(Name(b"F1"), font_id);
+        }
+        resources.finish();
+        page.finish();
+
+        // Image
+        let mut image = pdf.image_xobject(image_id, &img_data);
+        image.width(width as i32);
+        image.height(height as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+        image.filter(Filter::DctDecode);
+        image.finish();
+
+        // Content: image + invisible text
+        let mut content = Content::new();
+
+        // Draw image
+        content.save_state();
+        content.transform([page_width, 0.0, 0.0, page_height, 0.0, 0.0]);
+        content.x_object(Name(b"Im1"));
+        content.restore_state();
+
+        // Invisible text: each word at its OCR box, stretched to the box width.
+        // TSV coordinates are pixels from the top-left; PDF is points from the bottom-left.
+        if let Some(ocr) = page_data.ocr {
+            // Boxes are in the coordinates of the original (unrotated) page
+            let (ocr_width, ocr_height) = match ocr.pages.first() {
+                Some(p) if p.width > 0 && p.height > 0 => (p.width, p.height),
+                _ => (original_width, original_height),
+            };
+            let upright = |bbox: &BoundingBox| upright_box(bbox, rotation, ocr_width, ocr_height);
+            let (scale_x, scale_y) = match rotation {
+                90 | 270 => (page_width / ocr_height as f32, page_height / ocr_width as f32),
+                _ => (page_width / ocr_width as f32, page_height / ocr_height as f32),
+            };
+
+            content.begin_text();
+            content.set_text_rendering_mode(TextRenderingMode::Invisible);
+
+            for line in ocr.lines() {
+                let line_box = upright(&line.bbox);
+                let font_size = (line_box.height as f32 * scale_y).max(1.0);
+                let baseline = page_height - line_box.bottom() as f32 * scale_y;
+                content.set_font(Name(b"F1"), font_size);
+
+                for (i, word) in line.words.iter().enumerate() {
+                    // Trailing space lets viewers pick up word breaks
+                    let mut word_text = word.text.clone();
+                    if i + 1 < line.words.len() {
+                        word_text.push(' ');
+                    }
+
+                    let word_box = upright(&word.bbox);
+                    let box_width = word_box.width as f32 * scale_x;
+                    let natural_width = word_text.encode_utf16().count() as f32 * GLYPH_WIDTH / 1000.0 * font_size;
+                    let scaling = if natural_width > 0.0 { box_width / natural_width * 100.0 } else { 100.0 };
+
+                    content.set_horizontal_scaling(scaling);
+                    content.set_text_matrix([1.0, 0.0, 0.0, 1.0, word_box.left as f32 * scale_x, baseline]);
+                    content.show(Str(&encode_cids(&word_text, &mut used_cids)));
+                }
+            }
+
+            content.end_text();
+        }
+
+        pdf.stream(content_id, &content.finish());
+    }
+
+    if let Some(font_file) = font_file {
+        let cid_font_id = next_id.bump();
+        let descriptor_id = next_id.bump();
+        let to_unicode_id = next_id.bump();
+        let cid_to_gid_id = next_id.bump();
+        let font_file_id = next_id.bump();
+
+        // Font: Type0 / Identity-H so any Unicode text (incl. Cyrillic) round-trips
+        let system_info = SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        };
+
+        pdf.type0_font(font_id)
+            .base_font(Name(b"GlyphLessFont"))
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_font_id)
+            .to_unicode(to_unicode_id);
+
+        pdf.cid_font(cid_font_id)
+            .subtype(CidFontType::Type2)
+            .base_font(Name(b"GlyphLessFont"))
+            .system_info(system_info)
+            .font_descriptor(descriptor_id)
+            .default_width(GLYPH_WIDTH)
+            .cid_to_gid_map_stream(cid_to_gid_id);
+
+        // Ascent 1000 / descent 0: selection boxes match the word boxes
+        pdf.font_descriptor(descriptor_id)
+            .name(Name(b"GlyphLessFont"))
+            .flags(FontFlags::SYMBOLIC)
+            .bbox(Rect::new(0.0, 0.0, GLYPH_WIDTH, 1000.0))
+            .italic_angle(0.0)
+            .ascent(1000.0)
+            .descent(0.0)
+            .cap_height(1000.0)
+            .stem_v(80.0)
+            .font_file2(font_file_id);
+
+        pdf.stream(font_file_id, &font_file);
+
+        // Every CID maps to glyph 1 (the single empty glyph of the glyphless font)
+        let cid_to_gid: Vec<u8> = [0u8, 1].repeat(0x10000);
+        let cid_to_gid = compress(&cid_to_gid)?;
+        pdf.stream(cid_to_gid_id, &cid_to_gid).filter(Filter::FlateDecode);
+
+        // ToUnicode CMap makes search/copy return the recognized text
+        let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+        for (cid, c) in &used_cids {
+            cmap.pair(*cid, *c);
+        }
+        pdf.cmap(to_unicode_id, &cmap.finish());
+    }
 
     // Write to file
     std::fs::write(output_path, pdf.finish())?;
@@ -174,5 +358,62 @@ pub fn create_with_pdf_writer(
     Ok(())
 }
 
+/// `bbox` of a `width` x `height` page, in the page turned upright by
+/// `rotation` (clockwise, in degrees)
+fn upright_box(bbox: &BoundingBox, rotation: u32, width: u32, height: u32) -> BoundingBox {
+    match rotation {
+        90 => BoundingBox {
+            left: height.saturating_sub(bbox.bottom()),
+            top: bbox.left,
+            width: bbox.height,
+            height: bbox.width,
+        },
+        180 => BoundingBox {
+            left: width.saturating_sub(bbox.right()),
+            top: height.saturating_sub(bbox.bottom()),
+            ..*bbox
+        },
+        270 => BoundingBox {
+            left: bbox.top,
+            top: width.saturating_sub(bbox.right()),
+            width: bbox.height,
+            height: bbox.width,
+        },
+        _ => *bbox,
+    }
+}
 
+fn compress(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
 
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upright_box_follows_page_rotation() {
+        // 100 x 200 page, box 30 x 5 near the top-left corner
+        let bbox = BoundingBox { left: 10, top: 20, width: 30, height: 5 };
+
+        assert_eq!(upright_box(&bbox, 0, 100, 200), bbox);
+        assert_eq!(
+            upright_box(&bbox, 90, 100, 200),
+            BoundingBox { left: 175, top: 10, width: 5, height: 30 }
+        );
+        assert_eq!(
+            upright_box(&bbox, 180, 100, 200),
+            BoundingBox { left: 60, top: 175, width: 30, height: 5 }
+        );
+        assert_eq!(
+            upright_box(&bbox, 270, 100, 200),
+            BoundingBox { left: 20, top: 60, width: 5, height: 30 }
+        );
+    }
+}
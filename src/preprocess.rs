@@ -0,0 +1,591 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Luma};
+
+use crate::ocr_engine::{BoundingBox, OcrEngine};
+
+/// Long side of an A4 page in inches, used to estimate the resolution of a scan
+const PAGE_LONG_SIDE_INCHES: f32 = 11.69;
+
+/// Upscaling never enlarges an image more than this
+const MAX_UPSCALE: f32 = 4.0;
+
+/// Edge rows and columns mostly darker than this are treated as scanner border
+const BORDER_LUMA: u8 = 64;
+
+/// Largest skew searched for, in degrees
+const MAX_SKEW: f32 = 5.0;
+
+/// Skew below this is left alone, in degrees
+const MIN_SKEW: f32 = 0.1;
+
+/// Skew is estimated on a copy scaled down to this long side, in pixels
+const SKEW_SAMPLE_SIZE: u32 = 1000;
+
+/// Sauvola sensitivity and dynamic range of the standard deviation
+const SAUVOLA_K: f32 = 0.2;
+const SAUVOLA_R: f32 = 128.0;
+
+/// Numbers debug dumps so pages of different files never overwrite each other
+static DEBUG_SEQUENCE: AtomicUsize = AtomicUsize::new(1);
+
+/// Image preprocessing step run before OCR. Steps always run in the order
+/// declared here, however they are listed on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum PreprocessStep {
+    /// Convert to 8-bit grayscale (implied by every other step)
+    Grayscale,
+    /// Turn pages rotated by 90/180/270 degrees upright (Tesseract OSD)
+    Orient,
+    /// Straighten pages skewed by up to 5 degrees (projection profile)
+    Deskew,
+    /// Crop dark scanner borders
+    CropBorders,
+    /// Upscale low-resolution scans to the OCR DPI
+    Upscale,
+    /// Stretch contrast between the 1st and 99th percentile
+    Normalize,
+    /// Black and white (see `--binarization`)
+    Binarize,
+    /// Remove isolated specks (median filter)
+    Despeckle,
+}
+
+impl PreprocessStep {
+    fn name(&self) -> &'static str {
+        match self {
+            PreprocessStep::Grayscale => "grayscale",
+            PreprocessStep::Orient => "orient",
+            PreprocessStep::Deskew => "deskew",
+            PreprocessStep::CropBorders => "crop-borders",
+            PreprocessStep::Upscale => "upscale",
+            PreprocessStep::Normalize => "normalize",
+            PreprocessStep::Binarize => "binarize",
+            PreprocessStep::Despeckle => "despeckle",
+        }
+    }
+}
+
+/// Thresholding method of the binarize step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Binarization {
+    /// One global threshold; fine for clean flatbed scans
+    Otsu,
+    /// Local threshold per window; copes with shadows and uneven lighting in photos
+    #[default]
+    Sauvola,
+}
+
+/// Configured preprocessing chain; empty (the default) hands images to Tesseract untouched
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessing {
+    steps: Vec<PreprocessStep>,
+    binarization: Binarization,
+    debug_dir: Option<PathBuf>,
+}
+
+/// Preprocessed image and how to map its pixels back to the original image
+pub struct Preprocessed {
+    pub image: GrayImage,
+    /// Original image size
+    pub width: u32,
+    pub height: u32,
+    /// Clockwise rotation applied to turn the page upright, in degrees (0, 90, 180, 270)
+    pub rotation: u32,
+    /// Detected skew in degrees, clockwise positive; the page was rotated back by it
+    pub skew: f32,
+    /// Crop offset in deskewed pixels
+    left: u32,
+    top: u32,
+    /// Upscaling factor applied after cropping
+    scale: f32,
+}
+
+impl Preprocessed {
+    /// Box in the preprocessed image to the same region of the original image
+    /// (the bounding box of the region when the page was rotated)
+    pub fn to_original(&self, bbox: &BoundingBox) -> BoundingBox {
+        let corners = [
+            (bbox.left, bbox.top),
+            (bbox.right(), bbox.top),
+            (bbox.left, bbox.bottom()),
+            (bbox.right(), bbox.bottom()),
+        ]
+        .map(|(x, y)| self.point_to_original(x as f32, y as f32));
+
+        let left = corners.iter().map(|c| c.0).fold(f32::MAX, f32::min).max(0.0);
+        let top = corners.iter().map(|c| c.1).fold(f32::MAX, f32::min).max(0.0);
+        let right = corners.iter().map(|c| c.0).fold(0.0, f32::max).min(self.width as f32);
+        let bottom = corners.iter().map(|c| c.1).fold(0.0, f32::max).min(self.height as f32);
+        BoundingBox {
+            left: left.round() as u32,
+            top: top.round() as u32,
+            width: (right - left).max(0.0).round() as u32,
+            height: (bottom - top).max(0.0).round() as u32,
+        }
+    }
+
+    /// Undo the steps in reverse: upscale, crop, deskew, orientation
+    fn point_to_original(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = (x / self.scale + self.left as f32, y / self.scale + self.top as f32);
+
+        // Size of the upright page, which deskewing keeps
+        let (width, height) = match self.rotation {
+            90 | 270 => (self.height as f32, self.width as f32),
+            _ => (self.width as f32, self.height as f32),
+        };
+        let (x, y) = rotate_point(x, y, width / 2.0, height / 2.0, self.skew);
+
+        let (w, h) = (self.width as f32, self.height as f32);
+        match self.rotation {
+            90 => (y, h - x),
+            180 => (w - x, h - y),
+            270 => (w - y, x),
+            _ => (x, y),
+        }
+    }
+}
+
+impl Preprocessing {
+    pub fn new(steps: &[PreprocessStep]) -> Self {
+        let mut steps = steps.to_vec();
+        steps.sort();
+        steps.dedup();
+        Preprocessing {
+            steps,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_binarization(mut self, binarization: Binarization) -> Self {
+        self.binarization = binarization;
+        self
+    }
+
+    /// Save the image after every step to `dir` (for tuning the chain)
+    pub fn with_debug_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.debug_dir = dir;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Run the configured steps on the image at `path`; `dpi` is the resolution
+    /// Tesseract is told, which upscaling brings low-resolution scans up to
+    pub fn apply(&self, image: DynamicImage, path: &Path, dpi: u32) -> Result<Preprocessed, Box<dyn Error>> {
+        let mut result = Preprocessed {
+            width: image.width(),
+            height: image.height(),
+            image: image.into_luma8(),
+            rotation: 0,
+            skew: 0.0,
+            left: 0,
+            top: 0,
+            scale: 1.0,
+        };
+
+        let debug = self.debug_dir.as_ref().map(|dir| {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let sequence = DEBUG_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+            (dir, format!("{:04}_{}", sequence, stem))
+        });
+        if let Some((dir, prefix)) = &debug {
+            std::fs::create_dir_all(dir)?;
+            result.image.save(dir.join(format!("{}_0_input.png", prefix)))?;
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            match step {
+                PreprocessStep::Grayscale => {}
+                PreprocessStep::Orient => {
+                    let file = tempfile::Builder::new().prefix("ocr_osd_").suffix(".png").tempfile()?;
+                    result.image.save(file.path())?;
+                    match OcrEngine::detect_orientation(file.path(), dpi) {
+                        Ok(rotation) => {
+                            result.image = match rotation {
+                                90 => image::imageops::rotate90(&result.image),
+                                180 => image::imageops::rotate180(&result.image),
+                                270 => image::imageops::rotate270(&result.image),
+                                _ => result.image,
+                            };
+                            result.rotation = rotation;
+                        }
+                        // Missing osd.traineddata or too little text: OCR the page as it is
+                        Err(e) => log::debug!("Orientation detection failed for {}: {}", path.display(), e),
+                    }
+                }
+                PreprocessStep::Deskew => {
+                    let skew = estimate_skew(&result.image);
+                    if skew.abs() >= MIN_SKEW {
+                        result.image = rotate_about_center(&result.image, -skew);
+                        result.skew = skew;
+                    }
+                }
+                PreprocessStep::CropBorders => {
+                    let (left, top, width, height) = content_bounds(&result.image);
+                    if (width, height) != result.image.dimensions() {
+                        result.image = image::imageops::crop_imm(&result.image, left, top, width, height).to_image();
+                        result.left = left;
+                        result.top = top;
+                    }
+                }
+                PreprocessStep::Upscale => {
+                    let (width, height) = result.image.dimensions();
+                    // Resolution of the scan, assuming it covers a whole page
+                    let source_dpi = result.width.max(result.height) as f32 / PAGE_LONG_SIDE_INCHES;
+                    let scale = (dpi as f32 / source_dpi).min(MAX_UPSCALE);
+                    if scale > 1.1 {
+                        let scaled_width = (width as f32 * scale).round() as u32;
+                        let scaled_height = (height as f32 * scale).round() as u32;
+                        result.image = image::imageops::resize(&result.image, scaled_width, scaled_height, FilterType::CatmullRom);
+                        result.scale = scaled_width as f32 / width as f32;
+                    }
+                }
+                PreprocessStep::Normalize => normalize_contrast(&mut result.image),
+                PreprocessStep::Binarize => match self.binarization {
+                    Binarization::Otsu => {
+                        let threshold = otsu_threshold(&result.image);
+                        for pixel in result.image.pixels_mut() {
+                            pixel[0] = if pixel[0] > threshold { 255 } else { 0 };
+                        }
+                    }
+                    Binarization::Sauvola => sauvola(&mut result.image),
+                },
+                // Specks grow with upscaling, so does the window
+                PreprocessStep::Despeckle => {
+                    result.image = median_filter(&result.image, result.scale.round().max(1.0) as u32)
+                }
+            }
+
+            if let Some((dir, prefix)) = &debug {
+                result.image.save(dir.join(format!("{}_{}_{}.png", prefix, i + 1, step.name())))?;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// `(x, y)` rotated clockwise by `degrees` around `(cx, cy)` (y axis pointing down)
+fn rotate_point(x: f32, y: f32, cx: f32, cy: f32, degrees: f32) -> (f32, f32) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (dx, dy) = (x - cx, y - cy);
+    (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+}
+
+/// Image rotated clockwise by `degrees` around its center, same size, corners filled white
+fn rotate_about_center(image: &GrayImage, degrees: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    GrayImage::from_fn(width, height, |x, y| {
+        // Source pixel: the destination rotated back
+        let (sx, sy) = rotate_point(x as f32 + 0.5, y as f32 + 0.5, cx, cy, -degrees);
+        let (sx, sy) = (sx.floor(), sy.floor());
+        if sx < 0.0 || sy < 0.0 || sx >= width as f32 || sy >= height as f32 {
+            Luma([255])
+        } else {
+            *image.get_pixel(sx as u32, sy as u32)
+        }
+    })
+}
+
+/// Skew of the text lines in degrees (clockwise positive): the angle whose
+/// projection of dark pixels onto the line normal is most sharply peaked
+fn estimate_skew(image: &GrayImage) -> f32 {
+    let (width, height) = image.dimensions();
+    let factor = (SKEW_SAMPLE_SIZE as f32 / width.max(height) as f32).min(1.0);
+    let sample = image::imageops::resize(
+        image,
+        ((width as f32 * factor) as u32).max(1),
+        ((height as f32 * factor) as u32).max(1),
+        FilterType::Triangle,
+    );
+
+    let threshold = otsu_threshold(&sample);
+    let (cx, cy) = (sample.width() as f32 / 2.0, sample.height() as f32 / 2.0);
+    let dark: Vec<(f32, f32)> = sample
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[0] <= threshold)
+        .map(|(x, y, _)| (x as f32 - cx, y as f32 - cy))
+        .collect();
+    // Blank pages, or pages that are mostly dark (photos), have no lines to align
+    if dark.len() < 100 || dark.len() * 2 > (sample.width() * sample.height()) as usize {
+        return 0.0;
+    }
+
+    let diagonal = (cx.hypot(cy).ceil() as usize) + 1;
+    let score = |degrees: f32| {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut bins = vec![0u64; diagonal * 2];
+        for &(x, y) in &dark {
+            bins[(y * cos - x * sin + diagonal as f32) as usize] += 1;
+        }
+        bins.iter().map(|&n| n * n).sum::<u64>()
+    };
+    let best_in = |from: f32, to: f32, step: f32| {
+        let steps = ((to - from) / step).round() as i32;
+        (0..=steps)
+            .map(|i| from + i as f32 * step)
+            .max_by_key(|&angle| score(angle))
+            .unwrap_or(0.0)
+    };
+
+    // Coarse search, then refine around the best angle
+    let coarse = best_in(-MAX_SKEW, MAX_SKEW, 0.5);
+    best_in(coarse - 0.5, coarse + 0.5, 0.05)
+}
+
+/// Region left after trimming edge rows and columns that are mostly dark
+/// (scanner lid, book gutter); at most a quarter of each side is removed
+fn content_bounds(image: &GrayImage) -> (u32, u32, u32, u32) {
+    let (width, height) = image.dimensions();
+    let dark_row = |y: u32| (0..width).filter(|&x| image.get_pixel(x, y)[0] < BORDER_LUMA).count() * 2 > width as usize;
+    let dark_column = |x: u32| (0..height).filter(|&y| image.get_pixel(x, y)[0] < BORDER_LUMA).count() * 2 > height as usize;
+
+    let top = (0..height / 4).take_while(|&y| dark_row(y)).count() as u32;
+    let bottom = (0..height / 4).take_while(|&y| dark_row(height - 1 - y)).count() as u32;
+    let left = (0..width / 4).take_while(|&x| dark_column(x)).count() as u32;
+    let right = (0..width / 4).take_while(|&x| dark_column(width - 1 - x)).count() as u32;
+
+    (left, top, width - left - right, height - top - bottom)
+}
+
+fn histogram(image: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    histogram
+}
+
+/// Linear stretch so the 1st percentile becomes black and the 99th white
+fn normalize_contrast(image: &mut GrayImage) {
+    let histogram = histogram(image);
+    let total: u64 = histogram.iter().sum();
+    let percentile = |fraction: f64| {
+        let mut count = 0;
+        histogram
+            .iter()
+            .position(|&n| {
+                count += n;
+                count as f64 >= total as f64 * fraction
+            })
+            .unwrap_or(255) as f32
+    };
+
+    let (low, high) = (percentile(0.01), percentile(0.99));
+    if high <= low {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        pixel[0] = ((pixel[0] as f32 - low) * 255.0 / (high - low)).clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Threshold maximizing the between-class variance of the histogram
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let histogram = histogram(image);
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+
+    let (mut background, mut background_sum) = (0u64, 0f64);
+    let (mut best, mut best_variance) = (0u8, 0f64);
+    for (level, &count) in histogram.iter().enumerate() {
+        background += count;
+        background_sum += level as f64 * count as f64;
+        let foreground = total - background;
+        if background == 0 || foreground == 0 {
+            continue;
+        }
+        let background_mean = background_sum / background as f64;
+        let foreground_mean = (sum - background_sum) / foreground as f64;
+        let variance = background as f64 * foreground as f64 * (background_mean - foreground_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            best = level as u8;
+        }
+    }
+    best
+}
+
+/// Sauvola thresholding: T = mean * (1 + k * (stddev / R - 1)) over a window
+/// sized to the image, with window sums taken from integral images
+fn sauvola(image: &mut GrayImage) {
+    let (width, height) = image.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    let radius = (width.min(height) / 100).clamp(7, 40) as usize;
+
+    // Integral images with a zero row and column in front
+    let mut sums = vec![0u64; (w + 1) * (h + 1)];
+    let mut squares = vec![0u64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let (mut row_sum, mut row_square) = (0u64, 0u64);
+        for x in 0..w {
+            let value = image.get_pixel(x as u32, y as u32)[0] as u64;
+            row_sum += value;
+            row_square += value * value;
+            sums[(y + 1) * (w + 1) + x + 1] = sums[y * (w + 1) + x + 1] + row_sum;
+            squares[(y + 1) * (w + 1) + x + 1] = squares[y * (w + 1) + x + 1] + row_square;
+        }
+    }
+    let window = |table: &[u64], x0: usize, y0: usize, x1: usize, y1: usize| {
+        table[y1 * (w + 1) + x1] + table[y0 * (w + 1) + x0] - table[y0 * (w + 1) + x1] - table[y1 * (w + 1) + x0]
+    };
+
+    for y in 0..h {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(h));
+        for x in 0..w {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(w));
+            let count = ((x1 - x0) * (y1 - y0)) as f32;
+            let mean = window(&sums, x0, y0, x1, y1) as f32 / count;
+            let variance = (window(&squares, x0, y0, x1, y1) as f32 / count - mean * mean).max(0.0);
+            let threshold = mean * (1.0 + SAUVOLA_K * (variance.sqrt() / SAUVOLA_R - 1.0));
+
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            pixel[0] = if pixel[0] as f32 > threshold { 255 } else { 0 };
+        }
+    }
+}
+
+/// Median filter over a (2 * radius + 1) square window; edges reuse the nearest pixels
+fn median_filter(image: &GrayImage, radius: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let radius = radius as i64;
+    let mut neighbourhood = Vec::new();
+    GrayImage::from_fn(width, height, |x, y| {
+        neighbourhood.clear();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let nx = (x as i64 + dx).clamp(0, width as i64 - 1) as u32;
+                let ny = (y as i64 + dy).clamp(0, height as i64 - 1) as u32;
+                neighbourhood.push(image.get_pixel(nx, ny)[0]);
+            }
+        }
+        let middle = neighbourhood.len() / 2;
+        Luma([*neighbourhood.select_nth_unstable(middle).1])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// White page with `rects` (left, top, width, height, luma); the first match wins
+    fn page(width: u32, height: u32, rects: &[(u32, u32, u32, u32, u8)]) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let inside = rects.iter().find(|r| x >= r.0 && x < r.0 + r.2 && y >= r.1 && y < r.1 + r.3);
+            Luma([inside.map_or(255, |r| r.4)])
+        })
+    }
+
+    #[test]
+    fn boxes_map_back_through_crop_and_upscale() {
+        // 10 px scanner border on the left and top, a word at (60, 80)
+        let image = page(200, 300, &[(0, 0, 10, 300, 0), (0, 0, 200, 10, 0), (60, 80, 40, 20, 0)]);
+        let preprocessing = Preprocessing::new(&[PreprocessStep::Upscale, PreprocessStep::CropBorders]);
+        let result = preprocessing
+            .apply(DynamicImage::ImageLuma8(image), Path::new("scan.png"), 300)
+            .unwrap();
+
+        // 300 px over an A4 page is ~26 DPI, so the upscale is capped
+        assert_eq!((result.left, result.top, result.scale), (10, 10, MAX_UPSCALE));
+        assert_eq!(result.image.dimensions(), (760, 1160));
+        let word = BoundingBox { left: 200, top: 280, width: 160, height: 80 };
+        assert_eq!(result.to_original(&word), BoundingBox { left: 60, top: 80, width: 40, height: 20 });
+    }
+
+    #[test]
+    fn rotate_point_turns_clockwise_with_y_down() {
+        let (x, y) = rotate_point(20.0, 10.0, 10.0, 10.0, 90.0);
+        assert!((x - 10.0).abs() < 1e-4 && (y - 20.0).abs() < 1e-4);
+        let (x, y) = rotate_point(x, y, 10.0, 10.0, -90.0);
+        assert!((x - 20.0).abs() < 1e-4 && (y - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn otsu_separates_ink_from_paper() {
+        let threshold = otsu_threshold(&page(40, 40, &[(0, 0, 40, 10, 30), (0, 10, 40, 30, 220)]));
+        assert!((30..220).contains(&threshold));
+    }
+
+    #[test]
+    fn sauvola_copes_with_shadows() {
+        // Left half in shadow; a dark mark on each half
+        let mut image = page(100, 60, &[(20, 28, 3, 3, 40), (70, 28, 3, 3, 40), (0, 0, 50, 60, 120), (50, 0, 50, 60, 200)]);
+        sauvola(&mut image);
+        for (x, y) in [(21, 29), (71, 29)] {
+            assert_eq!(image.get_pixel(x, y)[0], 0, "mark at {}, {}", x, y);
+        }
+        for (x, y) in [(10, 10), (25, 29), (90, 10), (75, 29)] {
+            assert_eq!(image.get_pixel(x, y)[0], 255, "paper at {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn content_bounds_trim_dark_edges_up_to_a_quarter() {
+        let image = page(100, 80, &[(0, 0, 100, 5, 0), (92, 0, 8, 80, 20), (40, 40, 10, 10, 0)]);
+        assert_eq!(content_bounds(&image), (0, 5, 92, 75));
+        assert_eq!(content_bounds(&page(40, 40, &[(0, 0, 40, 40, 0)])), (10, 10, 20, 20));
+        assert_eq!(content_bounds(&page(40, 40, &[])), (0, 0, 40, 40));
+    }
+
+    #[test]
+    fn median_filter_removes_specks_but_keeps_strokes() {
+        let image = page(20, 20, &[(3, 3, 1, 1, 0), (10, 10, 5, 5, 0)]);
+        let filtered = median_filter(&image, 1);
+        assert_eq!(filtered.get_pixel(3, 3)[0], 255);
+        assert_eq!(filtered.get_pixel(12, 12)[0], 0);
+        assert_eq!(filtered.get_pixel(10, 12)[0], 0);
+    }
+
+    /// Bounding box of the dark pixels
+    fn dark_bounds(image: &GrayImage) -> BoundingBox {
+        let dark: Vec<(u32, u32)> = image.enumerate_pixels().filter(|p| p.2[0] < 128).map(|p| (p.0, p.1)).collect();
+        let left = dark.iter().map(|p| p.0).min().unwrap();
+        let top = dark.iter().map(|p| p.1).min().unwrap();
+        let right = dark.iter().map(|p| p.0).max().unwrap() + 1;
+        let bottom = dark.iter().map(|p| p.1).max().unwrap() + 1;
+        BoundingBox { left, top, width: right - left, height: bottom - top }
+    }
+
+    #[test]
+    fn skew_is_measured_clockwise() {
+        // Ten text lines across a page
+        let lines: Vec<_> = (0..10).map(|i| (60, 50 + i * 30, 480, 4, 0)).collect();
+        let upright = page(600, 400, &lines);
+        assert!(estimate_skew(&upright).abs() < MIN_SKEW);
+        for degrees in [2.0, -2.0] {
+            let skew = estimate_skew(&rotate_about_center(&upright, degrees));
+            assert!((skew - degrees).abs() <= 0.1, "{} estimated as {}", degrees, skew);
+        }
+    }
+
+    #[test]
+    fn boxes_map_back_through_orientation() {
+        let original = page(200, 100, &[(20, 10, 30, 5, 0)]);
+        for rotation in [90, 180, 270] {
+            let image = match rotation {
+                90 => image::imageops::rotate90(&original),
+                180 => image::imageops::rotate180(&original),
+                _ => image::imageops::rotate270(&original),
+            };
+            let upright = Preprocessed {
+                image,
+                width: 200,
+                height: 100,
+                rotation,
+                skew: 0.0,
+                left: 0,
+                top: 0,
+                scale: 1.0,
+            };
+            let word = dark_bounds(&upright.image);
+            assert_eq!(upright.to_original(&word), dark_bounds(&original), "rotation {}", rotation);
+        }
+    }
+}
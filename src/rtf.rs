@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use encoding_rs::Encoding;
+
+/// Destinations whose content is not document text
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "colortbl", "stylesheet", "info", "pict", "object", "objdata", "fldinst",
+    "themedata", "colorschememapping", "datastore", "latentstyles", "listtable",
+    "listoverridetable", "rsidtbl", "generator", "xmlnstbl", "mmathPr", "filetbl",
+    "revtbl", "pgdsctbl", "shppict", "nonshppict", "header", "footer", "headerl",
+    "headerr", "headerf", "footerl", "footerr", "footerf",
+];
+
+/// Formatting state scoped to an RTF group `{...}`
+#[derive(Clone)]
+struct Group {
+    skip: bool,
+    font_table: bool,
+    encoding: &'static Encoding,
+    /// Number of fallback characters after `\uN` (`\ucN`)
+    unicode_skip: usize,
+}
+
+/// Plain text of an RTF document. `\'xx` bytes are decoded with the document
+/// code page (`\ansicpg`) or the charset of the current font (`\fcharset`),
+/// `\uN` escapes are Unicode. Table cells end with '\t' and rows with '\n'.
+pub fn rtf_to_text(data: &[u8]) -> Result<String, Box<dyn Error>> {
+    if !data.starts_with(b"{\\rtf") {
+        return Err("Not an RTF document".into());
+    }
+
+    let mut text = String::new();
+    let mut pending = Vec::new();
+    let mut document_encoding = encoding_rs::WINDOWS_1252;
+    let mut font_encodings: HashMap<i32, &'static Encoding> = HashMap::new();
+    let mut table_font: Option<i32> = None;
+    let mut in_table = false;
+    // Fallback characters still to drop after a \uN escape
+    let mut fallback = 0usize;
+
+    let mut group = Group {
+        skip: false,
+        font_table: false,
+        encoding: document_encoding,
+        unicode_skip: 1,
+    };
+    let mut stack: Vec<Group> = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let byte = data[pos];
+        pos += 1;
+
+        match byte {
+            b'{' => {
+                stack.push(group.clone());
+                // A group starting with \* is an optional destination: skip unless known
+                if data[pos..].starts_with(b"\\*") {
+                    group.skip = true;
+                }
+            }
+            b'}' => {
+                if let Some(outer) = stack.pop() {
+                    flush(&mut pending, group.encoding, &mut text);
+                    group = outer;
+                }
+                // Fallback text never spans groups: {\u8364}E keeps the E
+                fallback = 0;
+            }
+            b'\\' => {
+                let Some(&next) = data.get(pos) else { break };
+
+                if !next.is_ascii_alphabetic() {
+                    pos += 1;
+                    if group.skip {
+                        if next == b'\'' {
+                            pos += 2;
+                        }
+                        continue;
+                    }
+                    match next {
+                        b'\'' => {
+                            let hex = data.get(pos..pos + 2).and_then(|h| std::str::from_utf8(h).ok());
+                            pos += 2;
+                            if let Some(value) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                                if fallback > 0 {
+                                    fallback -= 1;
+                                } else {
+                                    pending.push(value);
+                                }
+                            }
+                        }
+                        b'\\' | b'{' | b'}' => pending.push(next),
+                        b'~' => pending.push(b' '),
+                        b'_' => pending.push(b'-'),
+                        b'\r' | b'\n' => {
+                            flush(&mut pending, group.encoding, &mut text);
+                            text.push('\n');
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Control word: letters, optional signed number, optional space delimiter
+                let start = pos;
+                while pos < data.len() && data[pos].is_ascii_alphabetic() {
+                    pos += 1;
+                }
+                let word = std::str::from_utf8(&data[start..pos])?;
+                let number_start = pos;
+                if data.get(pos) == Some(&b'-') {
+                    pos += 1;
+                }
+                while pos < data.len() && data[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                let param: Option<i32> = std::str::from_utf8(&data[number_start..pos])?.parse().ok();
+                if data.get(pos) == Some(&b' ') {
+                    pos += 1;
+                }
+
+                if word == "bin" {
+                    pos += param.unwrap_or(0).max(0) as usize;
+                    continue;
+                }
+                if SKIPPED_DESTINATIONS.contains(&word) {
+                    group.skip = true;
+                }
+                if word == "fonttbl" {
+                    group.font_table = true;
+                    group.skip = true;
+                }
+
+                // The font table is skipped as text but read for font charsets
+                if group.font_table {
+                    match (word, param) {
+                        ("f", Some(font)) => table_font = Some(font),
+                        ("fcharset", Some(charset)) => {
+                            if let (Some(font), Some(encoding)) = (table_font, charset_encoding(charset)) {
+                                font_encodings.insert(font, encoding);
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if group.skip {
+                    continue;
+                }
+
+                match (word, param) {
+                    ("ansicpg", Some(code_page)) => {
+                        document_encoding = codepage_encoding(code_page);
+                        group.encoding = document_encoding;
+                    }
+                    ("mac", _) => {
+                        document_encoding = encoding_rs::MACINTOSH;
+                        group.encoding = document_encoding;
+                    }
+                    ("f", Some(font)) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        group.encoding = font_encodings.get(&font).copied().unwrap_or(document_encoding);
+                    }
+                    ("uc", Some(count)) => group.unicode_skip = count.max(0) as usize,
+                    ("u", Some(code)) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        let code = if code < 0 { code + 65536 } else { code };
+                        text.push(char::from_u32(code as u32).unwrap_or('\u{FFFD}'));
+                        fallback = group.unicode_skip;
+                    }
+                    ("pard", _) => in_table = false,
+                    ("intbl", _) => in_table = true,
+                    ("par" | "sect" | "page", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.push(if in_table { ' ' } else { '\n' });
+                    }
+                    ("line", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.push('\n');
+                    }
+                    ("tab", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.push('\t');
+                    }
+                    ("cell", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.truncate(text.trim_end_matches(' ').len());
+                        text.push('\t');
+                    }
+                    ("row", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.push('\n');
+                    }
+                    // Nested tables are flattened into their outer cell, as in DOCX
+                    ("nestcell" | "nestrow", _) => {
+                        flush(&mut pending, group.encoding, &mut text);
+                        text.push(' ');
+                    }
+                    _ => {
+                        if let Some(c) = special_character(word) {
+                            flush(&mut pending, group.encoding, &mut text);
+                            text.push(c);
+                        }
+                    }
+                }
+            }
+            b'\r' | b'\n' => {}
+            _ => {
+                if group.skip {
+                    continue;
+                }
+                if fallback > 0 {
+                    fallback -= 1;
+                } else {
+                    pending.push(byte);
+                }
+            }
+        }
+    }
+
+    flush(&mut pending, group.encoding, &mut text);
+    Ok(text)
+}
+
+/// Decode buffered code page bytes (multi-byte code pages need them together)
+fn flush(pending: &mut Vec<u8>, encoding: &'static Encoding, text: &mut String) {
+    if !pending.is_empty() {
+        let (decoded, _) = encoding.decode_without_bom_handling(pending);
+        text.push_str(&decoded);
+        pending.clear();
+    }
+}
+
+fn special_character(word: &str) -> Option<char> {
+    Some(match word {
+        "emdash" => '\u{2014}',
+        "endash" => '\u{2013}',
+        "bullet" => '\u{2022}',
+        "lquote" => '\u{2018}',
+        "rquote" => '\u{2019}',
+        "ldblquote" => '\u{201C}',
+        "rdblquote" => '\u{201D}',
+        "emspace" | "enspace" | "qmspace" => ' ',
+        _ => return None,
+    })
+}
+
+/// Windows code page number (`\ansicpg1251`) to encoding; unknown ones fall back to 1252
+fn codepage_encoding(code_page: i32) -> &'static Encoding {
+    match code_page {
+        866 => encoding_rs::IBM866,
+        874 => encoding_rs::WINDOWS_874,
+        932 => encoding_rs::SHIFT_JIS,
+        936 => encoding_rs::GBK,
+        949 => encoding_rs::EUC_KR,
+        950 => encoding_rs::BIG5,
+        1250 => encoding_rs::WINDOWS_1250,
+        1251 => encoding_rs::WINDOWS_1251,
+        1253 => encoding_rs::WINDOWS_1253,
+        1254 => encoding_rs::WINDOWS_1254,
+        1255 => encoding_rs::WINDOWS_1255,
+        1256 => encoding_rs::WINDOWS_1256,
+        1257 => encoding_rs::WINDOWS_1257,
+        1258 => encoding_rs::WINDOWS_1258,
+        10000 => encoding_rs::MACINTOSH,
+        10007 => encoding_rs::X_MAC_CYRILLIC,
+        20866 => encoding_rs::KOI8_R,
+        21866 => encoding_rs::KOI8_U,
+        28595 => encoding_rs::ISO_8859_5,
+        65001 => encoding_rs::UTF_8,
+        _ => encoding_rs::WINDOWS_1252,
+    }
+}
+
+/// Font charset (`\fcharset204`) to encoding; `None` means the document code page
+fn charset_encoding(charset: i32) -> Option<&'static Encoding> {
+    let code_page = match charset {
+        77 => 10000,
+        128 => 932,
+        129 => 949,
+        134 => 936,
+        136 => 950,
+        161 => 1253,
+        162 => 1254,
+        163 => 1258,
+        177 => 1255,
+        178 => 1256,
+        186 => 1257,
+        204 => 1251,
+        222 => 874,
+        238 => 1250,
+        _ => return None,
+    };
+    Some(codepage_encoding(code_page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_escapes_skip_their_fallback() {
+        assert_eq!(rtf_to_text(br"{\rtf1 caf\u233?s}").unwrap(), "caf\u{e9}s");
+        assert_eq!(rtf_to_text(br"{\rtf1\uc2 \u8364\'80\'80 5}").unwrap(), "\u{20ac} 5");
+        // Negative values are code units above 32767
+        assert_eq!(rtf_to_text(br"{\rtf1 \u-3913?}").unwrap(), "\u{f0b7}");
+        // A group ending early cuts the fallback short
+        assert_eq!(rtf_to_text(br"{\rtf1 {\uc3 \u8364}EUR}").unwrap(), "\u{20ac}EUR");
+    }
+
+    #[test]
+    fn hex_escapes_use_the_code_page_or_font_charset() {
+        assert_eq!(rtf_to_text(br"{\rtf1\ansi\ansicpg1251 \'cf\'f0\'e8}").unwrap(), "\u{41f}\u{440}\u{438}");
+        assert_eq!(rtf_to_text(br"{\rtf1\ansi caf\'e9}").unwrap(), "caf\u{e9}");
+        let data = br"{\rtf1\ansi{\fonttbl{\f0 Arial;}{\f1\fcharset204 Arial Cyr;}}\f0 a \f1\'e0}";
+        assert_eq!(rtf_to_text(data).unwrap(), "a \u{430}");
+    }
+
+    #[test]
+    fn skipped_destinations_are_not_text() {
+        let data = br"{\rtf1{\info{\title Secret}}{\*\unknown x}Body\par}";
+        assert_eq!(rtf_to_text(data).unwrap(), "Body\n");
+    }
+}
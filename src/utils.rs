@@ -1,10 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
 use std::path::Path;
-use image::GenericImageView;
+use image::metadata::Orientation;
+use image::{GenericImageView, ImageDecoder};
 
-use crate::file_processors::FileType;
+use crate::file_processors::{ExtractionMethod, FileType, PageResult};
+use crate::alto::render_alto;
+use crate::archive::ExtractionBudget;
+use crate::hocr::render_hocr;
+use crate::image_metadata;
+use crate::office_xml;
 use crate::OcrResult;
 
 /// Setup input and output directories
@@ -23,8 +29,73 @@ pub fn setup_directories(input_dir: &Path, output_dir: &Path) -> Result<(), Box<
     Ok(())
 }
 
-/// Extract metadata from file
-pub fn extract_metadata(file_path: &Path, file_type: &FileType) -> HashMap<String, String> {
+/// Decode an image by its content rather than its extension (a JPEG named .png),
+/// turned upright according to its EXIF orientation
+pub fn open_image(path: &Path) -> Result<image::DynamicImage, Box<dyn Error>> {
+    let mut decoder = image::ImageReader::open(path)?.with_guessed_format()?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = image::DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// EXIF orientation of an image; phone cameras store pixels as the sensor
+/// saw them and record in this tag how to turn them upright
+pub fn image_orientation(path: &Path) -> Orientation {
+    image::ImageReader::open(path)
+        .ok()
+        .and_then(|reader| reader.with_guessed_format().ok())
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+/// Rotation and skew corrected before OCR ("rotation", "skew_angle"); for
+/// documents with several OCR'd pages, listed per corrected page ("page 2: 90")
+pub fn correction_metadata(pages: &[PageResult]) -> HashMap<String, String> {
+    let ocr_pages: Vec<(usize, u32, f32)> = pages
+        .iter()
+        .filter_map(|p| p.ocr.as_ref().map(|ocr| (p.page_number, ocr.rotation, ocr.skew_angle)))
+        .collect();
+
+    let mut metadata = HashMap::new();
+    let mut record = |key: &str, values: Vec<(usize, String)>| {
+        if values.is_empty() {
+            return;
+        }
+        let value = if ocr_pages.len() == 1 {
+            values[0].1.clone()
+        } else {
+            values
+                .iter()
+                .map(|(page, value)| format!("page {}: {}", page, value))
+                .collect::<Vec<_>>()
+                .join("; ")
+        };
+        metadata.insert(key.to_string(), value);
+    };
+
+    record(
+        "rotation",
+        ocr_pages
+            .iter()
+            .filter(|(_, rotation, _)| *rotation != 0)
+            .map(|(page, rotation, _)| (*page, rotation.to_string()))
+            .collect(),
+    );
+    record(
+        "skew_angle",
+        ocr_pages
+            .iter()
+            .filter(|(_, _, skew)| *skew != 0.0)
+            .map(|(page, _, skew)| (*page, format!("{:.2}", skew)))
+            .collect(),
+    );
+    metadata
+}
+
+/// Extract metadata from file; document packages are read within the upload's extraction budget
+pub fn extract_metadata(file_path: &Path, file_type: &FileType, budget: &ExtractionBudget) -> HashMap<String, String> {
     let mut metadata = HashMap::new();
 
     metadata.insert("path".to_string(), file_path.display().to_string());
@@ -39,11 +110,13 @@ pub fn extract_metadata(file_path: &Path, file_type: &FileType) -> HashMap<Strin
     // Add type-specific metadata
     match file_type {
         FileType::Image(_) => {
-            if let Ok(img) = image::open(file_path) {
+            if let Ok(img) = open_image(file_path) {
                 let (width, height) = GenericImageView::dimensions(&img);
                 metadata.insert("dimensions".to_string(), format!("{}x{}", width, height));
                 metadata.insert("color_type".to_string(), format!("{:?}", img.color()));
             }
+            // Camera, capture time, GPS, DPI
+            metadata.extend(image_metadata::exif_properties(file_path));
         }
         FileType::Pdf => {
             // PDF metadata would go here
@@ -51,35 +124,120 @@ pub fn extract_metadata(file_path: &Path, file_type: &FileType) -> HashMap<Strin
         }
         FileType::Docx => {
             metadata.insert("type".to_string(), "Word Document".to_string());
+            // Pages, words, characters, title, author, created/modified dates
+            metadata.extend(office_xml::document_properties(file_path, budget));
+        }
+        FileType::Odt => {
+            metadata.insert("type".to_string(), "OpenDocument Text".to_string());
+        }
+        FileType::Rtf => {
+            metadata.insert("type".to_string(), "Rich Text Document".to_string());
+        }
+        FileType::Eml => {
+            metadata.insert("type".to_string(), "Email Message".to_string());
         }
-        FileType::Xlsx | FileType::Xls => {
+        FileType::Pptx => {
+            metadata.insert("type".to_string(), "PowerPoint Presentation".to_string());
+            metadata.extend(office_xml::document_properties(file_path, budget));
+        }
+        FileType::Xlsx | FileType::Xls | FileType::Xlsb => {
             metadata.insert("type".to_string(), "Excel Spreadsheet".to_string());
         }
+        FileType::Ods => {
+            metadata.insert("type".to_string(), "OpenDocument Spreadsheet".to_string());
+        }
         _ => {}
     }
 
     metadata
 }
 
-/// Save processing results to disk
+/// Optional outputs written by `save_results` next to results.csv and metadata.json
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputOptions {
+    /// texts/<file>.txt
+    pub save_texts: bool,
+    /// hocr/<file>.hocr for OCR'd documents
+    pub hocr: bool,
+    /// alto/<file>.xml (or <file>_<page>.xml) for every OCR'd page
+    pub alto: bool,
+    /// tables/<file>/<sheet>.csv for spreadsheets
+    pub tables: bool,
+}
+
+/// Escape text for XML/HTML content and attribute values
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Base name for per-document output files. Archive entries keep their
+/// container path so names don't collide (`a.zip!/x/scan.png` → `a.zip_x_scan`)
+fn output_base_name(filename: &str) -> String {
+    let (container, name) = match filename.rfind('/') {
+        Some(i) => (&filename[..i], &filename[i + 1..]),
+        None => ("", filename),
+    };
+    let stem = Path::new(name).file_stem().unwrap_or_default().to_string_lossy();
+
+    if container.is_empty() {
+        stem.to_string()
+    } else {
+        let container = container.trim_end_matches('!').replace("!/", "_").replace(['/', '\\'], "_");
+        format!("{}_{}", container, stem)
+    }
+}
+
+/// Directory name for the tables of a document. Unlike `output_base_name` it keeps
+/// the extension, so `report.xlsx` and `report.ods` don't share a directory.
+fn table_dir_name(filename: &str) -> String {
+    sanitize_file_name(&filename.replace("!/", "_"))
+}
+
+/// Make a sheet name safe to use as a file name
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect();
+    let sanitized = sanitized.trim().trim_matches('.').to_string();
+
+    if sanitized.is_empty() { "sheet".to_string() } else { sanitized }
+}
+
 /// Save processing results to disk
 pub fn save_results(
     results: &[OcrResult],
     output_dir: &Path,
-    save_individual_files: bool,
+    options: &OutputOptions,
 ) -> Result<(), Box<dyn Error>> {
     // Save to CSV (without metadata field)
     let csv_path = output_dir.join("results.csv");
     let mut wtr = csv::Writer::from_path(&csv_path)?;
 
     // Write header manually
-    wtr.write_record(&["filename", "file_type", "page_count", "text_length", "processing_time_ms", "error"])?;
+    wtr.write_record(["filename", "file_type", "page_count", "ocr_pages", "text_length", "processing_time_ms", "error"])?;
 
     for result in results {
+        let ocr_pages = result.pages.iter()
+            .filter(|p| p.method == ExtractionMethod::Ocr)
+            .count();
+
         wtr.write_record(&[
             &result.filename,
             &result.file_type,
             &result.page_count.to_string(),
+            &ocr_pages.to_string(),
             &result.text.len().to_string(),  // ✅ Змінено: довжина замість повного тексту
             &result.processing_time_ms.to_string(),
             &result.error.as_ref().unwrap_or(&String::new()),
@@ -88,17 +246,35 @@ pub fn save_results(
     wtr.flush()?;
     log::info!("Results saved to: {}", csv_path.display());
 
-    if save_individual_files {
+    // Save per-page records (one row per page or sheet) for page citations
+    let pages_csv_path = output_dir.join("pages.csv");
+    let mut wtr = csv::Writer::from_path(&pages_csv_path)?;
+    wtr.write_record(["filename", "page_number", "label", "method", "char_count", "confidence", "language"])?;
+
+    for result in results {
+        for page in &result.pages {
+            wtr.write_record([
+                result.filename.as_str(),
+                &page.page_number.to_string(),
+                page.label.as_deref().unwrap_or_default(),
+                page.method.as_str(),
+                &page.char_count.to_string(),
+                &page.confidence.map(|c| format!("{:.1}", c)).unwrap_or_default(),
+                page.language.as_deref().unwrap_or_default(),
+            ])?;
+        }
+    }
+    wtr.flush()?;
+    log::info!("Page results saved to: {}", pages_csv_path.display());
+
+    if options.save_texts {
         // Save individual text files
         let texts_dir = output_dir.join("texts");
         fs::create_dir_all(&texts_dir)?;
 
         for result in results {
             if result.error.is_none() && !result.text.is_empty() {
-                let base_name = Path::new(&result.filename)
-                    .file_stem()
-                    .unwrap_or_default()
-                    .to_string_lossy();
+                let base_name = output_base_name(&result.filename);
                 let text_path = texts_dir.join(format!("{}.txt", base_name));
                 fs::write(&text_path, &result.text)?;
             }
@@ -107,6 +283,90 @@ pub fn save_results(
         log::info!("Text files saved to: {}", texts_dir.display());
     }
 
+    if options.hocr {
+        let hocr_dir = output_dir.join("hocr");
+        fs::create_dir_all(&hocr_dir)?;
+
+        for result in results {
+            if result.error.is_some() {
+                continue;
+            }
+
+            if let Some(hocr) = render_hocr(&result.filename, &result.pages) {
+                let base_name = output_base_name(&result.filename);
+                fs::write(hocr_dir.join(format!("{}.hocr", base_name)), hocr)?;
+            }
+        }
+
+        log::info!("hOCR files saved to: {}", hocr_dir.display());
+    }
+
+    if options.alto {
+        let alto_dir = output_dir.join("alto");
+        fs::create_dir_all(&alto_dir)?;
+
+        for result in results {
+            if result.error.is_some() {
+                continue;
+            }
+
+            let base_name = output_base_name(&result.filename);
+            let ocr_page_count = result.pages.iter().filter(|p| p.ocr.is_some()).count();
+
+            for page in &result.pages {
+                if let Some(alto) = render_alto(&result.filename, page) {
+                    let alto_name = if ocr_page_count > 1 {
+                        format!("{}_{:04}.xml", base_name, page.page_number)
+                    } else {
+                        format!("{}.xml", base_name)
+                    };
+                    fs::write(alto_dir.join(alto_name), alto)?;
+                }
+            }
+        }
+
+        log::info!("ALTO files saved to: {}", alto_dir.display());
+    }
+
+    if options.tables {
+        let tables_dir = output_dir.join("tables");
+
+        for result in results {
+            if result.error.is_some() {
+                continue;
+            }
+
+            // "Q1/2" and "Q1:2" sanitize to the same name; later sheets get a suffix.
+            // Compared case-insensitively for case-insensitive file systems.
+            let mut used_names = HashSet::new();
+
+            for page in &result.pages {
+                let Some(table) = &page.table else { continue };
+
+                let file_dir = tables_dir.join(table_dir_name(&result.filename));
+                fs::create_dir_all(&file_dir)?;
+
+                let sheet_name = page.label.clone().unwrap_or_else(|| format!("sheet{}", page.page_number));
+                let base_name = sanitize_file_name(&sheet_name);
+                let mut file_name = base_name.clone();
+                let mut suffix = 1;
+                while !used_names.insert(file_name.to_lowercase()) {
+                    suffix += 1;
+                    file_name = format!("{}_{}", base_name, suffix);
+                }
+                let csv_path = file_dir.join(format!("{}.csv", file_name));
+
+                let mut wtr = csv::WriterBuilder::new().flexible(true).from_path(&csv_path)?;
+                for row in table {
+                    wtr.write_record(row)?;
+                }
+                wtr.flush()?;
+            }
+        }
+
+        log::info!("Tables saved to: {}", tables_dir.display());
+    }
+
     // Save full metadata as JSON (включаючи metadata)
     let json_path = output_dir.join("metadata.json");
     let json_data = serde_json::to_string_pretty(&results)?;